@@ -0,0 +1,275 @@
+use std::collections::{BTreeMap, BTreeSet};
+use std::fs;
+use std::path::Path;
+use std::sync::Mutex;
+
+use serde_derive::{Deserialize, Serialize};
+
+use crate::chunk::Chunk;
+use crate::config::{Config, DbConfig};
+use crate::dependency::LoadOrder;
+use crate::error::{CustomError, CustomResult, Phase};
+use crate::logger::Logger;
+
+static CHECKPOINT: Mutex<Option<CheckpointState>> = Mutex::new(None);
+
+struct CheckpointState {
+    path: String,
+    resumed: bool,
+    file: CheckpointFile,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct CheckpointFile {
+    /// Databases the progress belongs to, so it is never applied to another target.
+    source: String,
+    target: String,
+    tables: BTreeMap<String, TableProgress>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Default)]
+struct TableProgress {
+    /// Finished steps, named after `Phase`.
+    #[serde(default)]
+    completed: BTreeSet<String>,
+    /// Conditions of the chunks the data was split into. A resumed run copies the
+    /// same ranges, whatever the table holds by then.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    chunks: Vec<String>,
+    #[serde(default, skip_serializing_if = "BTreeSet::is_empty")]
+    copied_chunks: BTreeSet<usize>,
+}
+
+impl TableProgress {
+    fn reset_data(&mut self) {
+        self.completed.remove(&Phase::Data.to_string());
+        self.completed.remove(&Phase::Sequence.to_string());
+        self.chunks.clear();
+        self.copied_chunks.clear();
+    }
+}
+
+/// Progress of the run, saved after every finished step when `--checkpoint` is given
+/// so a failed run can be picked up with `--resume`.
+#[derive(Debug)]
+pub struct Checkpoint {}
+
+impl Checkpoint {
+    pub fn new() -> Self {
+        Self {}
+    }
+
+    /// Starts a new checkpoint at `path`, or with `resume` continues the one there.
+    pub fn init(path: String, resume: bool, config: &Config) -> CustomResult<()> {
+        let logger = Logger::new();
+        let source = describe(&config.source);
+        let target = describe(&config.target);
+
+        let resumed = resume && Path::new(&path).exists();
+        let file = if resumed {
+            let content = fs::read_to_string(&path).map_err(|err| {
+                CustomError::Config(format!("Failed to read checkpoint {}: {}", path, err))
+            })?;
+            let file: CheckpointFile = serde_json::from_str(&content).map_err(|err| {
+                CustomError::Config(format!("Invalid checkpoint {}: {}", path, err))
+            })?;
+            if file.source != source || file.target != target {
+                return Err(CustomError::Config(format!(
+                    "Checkpoint {} belongs to a copy from {} to {}",
+                    path, file.source, file.target
+                )));
+            }
+            logger.info(format!("Resuming from checkpoint {}", path).as_str());
+            file
+        } else {
+            if resume {
+                logger.warn(format!("No checkpoint at {}, starting from scratch", path).as_str());
+            }
+            CheckpointFile {
+                source,
+                target,
+                tables: BTreeMap::new(),
+            }
+        };
+
+        let state = CheckpointState {
+            path,
+            resumed,
+            file,
+        };
+        save(&state);
+        *CHECKPOINT.lock().unwrap() = Some(state);
+        Ok(())
+    }
+
+    fn with_state<T: Default>(&self, read: impl FnOnce(&mut CheckpointState) -> T) -> T {
+        match CHECKPOINT.lock().unwrap().as_mut() {
+            Some(state) => read(state),
+            None => T::default(),
+        }
+    }
+
+    fn update(&self, table: &str, change: impl FnOnce(&mut TableProgress)) {
+        self.with_state(|state| {
+            change(state.file.tables.entry(table.to_string()).or_default());
+            save(state);
+        });
+    }
+
+    /// Whether this run continues an earlier one, whose objects may already exist.
+    pub fn is_resuming(&self) -> bool {
+        self.with_state(|state| state.resumed && !state.file.tables.is_empty())
+    }
+
+    pub fn is_done(&self, table: &str, phase: Phase) -> bool {
+        self.with_state(|state| {
+            state
+                .file
+                .tables
+                .get(table)
+                .is_some_and(|progress| progress.completed.contains(&phase.to_string()))
+        })
+    }
+
+    /// Records a finished step. A re-created table starts its data over.
+    pub fn complete(&self, table: &str, phase: Phase) {
+        self.update(table, |progress| {
+            if phase == Phase::Structure {
+                progress.reset_data();
+            }
+            progress.completed.insert(phase.to_string());
+        });
+    }
+
+    /// Returns the tables whose data an earlier run already copied. Tables that start
+    /// over are emptied first, which also takes the rows of tables referencing them
+    /// (`TRUNCATE ... CASCADE`), so those start over as well.
+    pub fn prepare_data(&self, order: &LoadOrder) -> BTreeSet<String> {
+        let data = Phase::Data.to_string();
+        self.with_state(|state| {
+            let tables = &mut state.file.tables;
+            let kept = |table: &String| {
+                tables.get(table).is_some_and(|progress| {
+                    progress.completed.contains(&data) || !progress.copied_chunks.is_empty()
+                })
+            };
+            let restarted: BTreeSet<String> = order
+                .tables
+                .iter()
+                .filter(|table| !kept(table))
+                .cloned()
+                .collect();
+
+            for table in restarted.iter().chain(&order.dependents(&restarted)) {
+                if let Some(progress) = tables.get_mut(table) {
+                    progress.reset_data();
+                }
+            }
+            save(state);
+
+            state
+                .file
+                .tables
+                .iter()
+                .filter(|(_, progress)| progress.completed.contains(&data))
+                .map(|(table, _)| table.clone())
+                .collect()
+        })
+    }
+
+    /// Whether some chunks of `table` are in the target already, so it must not be emptied.
+    pub fn has_copied_chunks(&self, table: &str) -> bool {
+        self.with_state(|state| {
+            state
+                .file
+                .tables
+                .get(table)
+                .is_some_and(|progress| !progress.copied_chunks.is_empty())
+        })
+    }
+
+    /// Chunks of an earlier run's split of `table` that are not copied yet.
+    pub fn remaining_chunks(&self, table: &str) -> Option<Vec<Chunk>> {
+        self.with_state(|state| {
+            let progress = state.file.tables.get(table)?;
+            if progress.chunks.is_empty() {
+                return None;
+            }
+            let count = progress.chunks.len();
+            let chunks = progress
+                .chunks
+                .iter()
+                .enumerate()
+                .filter(|(index, _)| !progress.copied_chunks.contains(index))
+                .map(|(index, condition)| Chunk {
+                    index,
+                    count,
+                    condition: condition.clone(),
+                })
+                .collect();
+            Some(chunks)
+        })
+    }
+
+    pub fn plan_chunks(&self, table: &str, chunks: &[Chunk]) {
+        self.update(table, |progress| {
+            progress.chunks = chunks.iter().map(|chunk| chunk.condition.clone()).collect();
+            progress.copied_chunks.clear();
+        });
+    }
+
+    /// Records a committed chunk; the table's data is done once all of them are.
+    pub fn chunk_copied(&self, table: &str, chunk: &Chunk) {
+        self.update(table, |progress| {
+            progress.copied_chunks.insert(chunk.index);
+            if progress.copied_chunks.len() == progress.chunks.len() {
+                progress.completed.insert(Phase::Data.to_string());
+            }
+        });
+    }
+
+    /// Removes the checkpoint after a successful run; otherwise it is kept for `--resume`.
+    pub fn finish(&self, result: &CustomResult<()>) {
+        let logger = Logger::new();
+        let Some(state) = CHECKPOINT.lock().unwrap().take() else {
+            return;
+        };
+
+        if result.is_err() {
+            logger.info(
+                format!(
+                    "Progress saved to {}, rerun with --resume to continue",
+                    state.path
+                )
+                .as_str(),
+            );
+            return;
+        }
+        if let Err(err) = fs::remove_file(&state.path) {
+            logger.error(format!("Failed to remove checkpoint {}: {}", state.path, err).as_str());
+        }
+    }
+}
+
+fn describe(db_config: &DbConfig) -> String {
+    let mut name = format!(
+        "{}:{}/{}",
+        db_config.host, db_config.port, db_config.database
+    );
+    if let Some(schema) = &db_config.schema {
+        name = format!("{}.{}", name, schema);
+    }
+    name
+}
+
+// Written next to the file and renamed over it, so a crash never leaves half a checkpoint.
+fn save(state: &CheckpointState) {
+    let temporary = format!("{}.tmp", state.path);
+    let written = serde_json::to_string_pretty(&state.file)
+        .map_err(|err| err.to_string())
+        .and_then(|json| fs::write(&temporary, json).map_err(|err| err.to_string()))
+        .and_then(|_| fs::rename(&temporary, &state.path).map_err(|err| err.to_string()));
+    if let Err(err) = written {
+        Logger::new().error(format!("Failed to write checkpoint {}: {}", state.path, err).as_str());
+    }
+}
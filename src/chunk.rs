@@ -0,0 +1,148 @@
+use std::fmt;
+
+/// Part of a table copied on its own: a half-open range of its integer key (or, on
+/// postgres, its heap pages).
+#[derive(Debug, Clone)]
+pub struct Chunk {
+    pub index: usize,
+    pub count: usize,
+    /// SQL condition selecting the rows of this chunk.
+    pub condition: String,
+}
+
+impl fmt::Display for Chunk {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "chunk {}/{}", self.index + 1, self.count)
+    }
+}
+
+/// Splits the inclusive `min..=max` range of `expression` into at most `count` chunks
+/// of equal width, rendering bounds with `literal`. The first and last chunks are
+/// open-ended, so rows outside the range seen while splitting are still copied; an
+/// empty table (`None`) is a single chunk.
+pub fn split(
+    range: Option<(i64, i64)>,
+    count: usize,
+    expression: &str,
+    literal: impl Fn(i64) -> String,
+) -> Vec<Chunk> {
+    let mut bounds = Vec::new();
+    if let Some((min, max)) = range {
+        let count = count.max(1) as i128;
+        let width = (max as i128 - min as i128 + 1).max(1);
+        let size = (width + count - 1) / count;
+        let mut bound = min as i128 + size;
+        while bound <= max as i128 {
+            bounds.push(bound as i64);
+            bound += size;
+        }
+    }
+
+    let count = bounds.len() + 1;
+    let mut from = None;
+    let mut chunks = Vec::with_capacity(count);
+    for index in 0..count {
+        let to = bounds.get(index).copied();
+        let condition = match (from, to) {
+            (Some(from), Some(to)) => format!(
+                "{0} >= {1} AND {0} < {2}",
+                expression,
+                literal(from),
+                literal(to)
+            ),
+            (Some(from), None) => format!("{} >= {}", expression, literal(from)),
+            (None, Some(to)) => format!("{} < {}", expression, literal(to)),
+            (None, None) => "TRUE".to_string(),
+        };
+        chunks.push(Chunk {
+            index,
+            count,
+            condition,
+        });
+        from = to;
+    }
+    chunks
+}
+
+#[cfg(test)]
+mod tests {
+    use super::split;
+
+    fn conditions(range: Option<(i64, i64)>, count: usize) -> Vec<String> {
+        split(range, count, "id", |value| value.to_string())
+            .into_iter()
+            .map(|chunk| chunk.condition)
+            .collect()
+    }
+
+    #[test]
+    fn empty_table_is_one_chunk() {
+        assert_eq!(conditions(None, 4), ["TRUE"]);
+    }
+
+    #[test]
+    fn single_value_is_one_chunk() {
+        assert_eq!(conditions(Some((5, 5)), 4), ["TRUE"]);
+    }
+
+    #[test]
+    fn zero_count_is_one_chunk() {
+        assert_eq!(conditions(Some((1, 10)), 0), ["TRUE"]);
+    }
+
+    #[test]
+    fn range_splits_into_equal_open_ended_chunks() {
+        let chunks = split(Some((1, 100)), 4, "id", |value| value.to_string());
+
+        assert_eq!(
+            chunks
+                .iter()
+                .map(|chunk| chunk.condition.as_str())
+                .collect::<Vec<_>>(),
+            [
+                "id < 26",
+                "id >= 26 AND id < 51",
+                "id >= 51 AND id < 76",
+                "id >= 76"
+            ]
+        );
+        assert_eq!(chunks[2].index, 2);
+        assert_eq!(chunks[2].count, 4);
+        assert_eq!(chunks[2].to_string(), "chunk 3/4");
+    }
+
+    #[test]
+    fn range_smaller_than_count_gives_one_chunk_per_value() {
+        assert_eq!(
+            conditions(Some((1, 3)), 8),
+            ["id < 2", "id >= 2 AND id < 3", "id >= 3"]
+        );
+    }
+
+    #[test]
+    fn bounds_near_i64_limits_do_not_overflow() {
+        assert_eq!(
+            conditions(Some((i64::MIN, i64::MAX)), 2),
+            ["id < 0", "id >= 0"]
+        );
+        assert_eq!(
+            conditions(Some((i64::MAX - 1, i64::MAX)), 4),
+            [format!("id < {}", i64::MAX), format!("id >= {}", i64::MAX)]
+        );
+        assert_eq!(
+            conditions(Some((i64::MIN, i64::MIN + 1)), 4),
+            [
+                format!("id < {}", i64::MIN + 1),
+                format!("id >= {}", i64::MIN + 1)
+            ]
+        );
+    }
+
+    #[test]
+    fn literal_renders_the_bounds() {
+        let chunks = split(Some((0, 9)), 2, "\"id\"", |value| format!("'{}'", value));
+
+        assert_eq!(chunks[0].condition, "\"id\" < '5'");
+        assert_eq!(chunks[1].condition, "\"id\" >= '5'");
+    }
+}
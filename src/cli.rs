@@ -1,8 +1,88 @@
-use clap::Parser;
+use clap::{Parser, Subcommand};
+
+use crate::config::RunOptions;
+use crate::diff::DiffFormat;
+use crate::registry::Task;
 
 #[derive(Parser, Debug)]
 #[command(version, about, long_about = None)]
 pub struct CLi {
+    #[command(subcommand)]
+    pub command: Option<Command>,
+
+    /// Path to the TOML config file
     #[arg(short, long)]
     pub path: String,
+
+    /// Name of a `[profiles.<name>]` table to apply on top of the base config
+    #[arg(long)]
+    pub profile: Option<String>,
+
+    /// Stop at the first table that fails
+    #[arg(long)]
+    pub fail_fast: bool,
+
+    /// Continue with the remaining tables after a failure (default)
+    #[arg(long, conflicts_with = "fail_fast")]
+    pub keep_going: bool,
+
+    /// Write a JSON run report to this path
+    #[arg(long)]
+    pub report: Option<String>,
+
+    /// Print every statement the migration would run without changing the target
+    #[arg(long)]
+    pub dry_run: bool,
+
+    /// Skip the interactive confirmation of the target database
+    #[arg(short, long)]
+    pub yes: bool,
+
+    /// Also write the dry-run plan to this `.sql` file
+    #[arg(long, requires = "dry_run")]
+    pub plan_file: Option<String>,
+
+    /// Save the progress of the run to this file after every finished step
+    #[arg(long, conflicts_with = "dry_run")]
+    pub checkpoint: Option<String>,
+
+    /// Skip the work an earlier failed run recorded in the `--checkpoint` file
+    #[arg(long, requires = "checkpoint")]
+    pub resume: bool,
+
+    /// Compare row counts and checksums of the copied tables after the copy
+    #[arg(long, conflicts_with = "dry_run")]
+    pub verify: bool,
+}
+
+/// Runs something other than the copy; without one the copy runs.
+#[derive(Subcommand, Debug, Clone)]
+pub enum Command {
+    /// Compare row counts and checksums of the tables without copying anything
+    Verify,
+    /// Print how the structure of the target differs from the source
+    Diff {
+        /// Output as human readable text or JSON
+        #[arg(long, value_enum, default_value = "text")]
+        format: DiffFormat,
+    },
+}
+
+impl CLi {
+    pub fn run_options(&self) -> RunOptions {
+        RunOptions {
+            fail_fast: self.fail_fast,
+            dry_run: self.dry_run,
+            assume_yes: self.yes,
+            verify: self.verify,
+        }
+    }
+
+    pub fn task(&self) -> Task {
+        match self.command {
+            None => Task::Migrate,
+            Some(Command::Verify) => Task::Verify,
+            Some(Command::Diff { format }) => Task::Diff(format),
+        }
+    }
 }
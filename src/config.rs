@@ -1,40 +1,370 @@
+use percent_encoding::percent_decode_str;
 use serde_derive::Deserialize;
+use url::Url;
 
+use std::collections::BTreeMap;
+use std::fmt;
 use std::fs;
 
+use crate::credentials;
+use crate::error::{CustomError, CustomResult};
 use crate::logger::LogLevel;
 
-const CONFIG_FILE: &str = "config.toml";
+const PROFILES_KEY: &str = "profiles";
+const REDACTED: &str = "<redacted>";
+const DEFAULT_POOL_SIZE: u32 = 10;
 
 #[derive(Debug, Deserialize, Clone)]
 pub struct TablesConfig {
     pub data_source: Vec<String>,
     pub skip: Vec<String>,
+    /// Per-table overrides, keyed by table name (`[tables.settings.<table>]`).
+    #[serde(default)]
+    pub settings: BTreeMap<String, TableSettings>,
 }
 
-#[derive(Debug, Deserialize, Clone)]
+impl TablesConfig {
+    pub fn settings(&self, table: &str) -> TableSettings {
+        self.settings.get(table).cloned().unwrap_or_default()
+    }
+}
+
+#[derive(Debug, Deserialize, Clone, Default)]
+pub struct TableSettings {
+    #[serde(default)]
+    pub transfer: TransferMode,
+    /// Key (or postgres page) ranges copied side by side; 0 or 1 copies the table whole.
+    #[serde(default)]
+    pub chunks: usize,
+    /// Read every chunk from one snapshot of the source; implied by
+    /// `technology.consistent_snapshot`.
+    #[serde(default)]
+    pub snapshot: bool,
+    /// Column (e.g. `updated_at`, or an increasing primary key) an incremental copy
+    /// continues from: the table is not truncated and only rows above the value
+    /// stored by the last run are copied, updating rows that already exist.
+    pub watermark: Option<String>,
+    #[serde(default)]
+    pub load: LoadMode,
+    /// Columns upserted rows are matched on; the primary key when empty.
+    #[serde(default)]
+    pub key: Vec<String>,
+}
+
+impl TableSettings {
+    /// Whether the target table is emptied before it is loaded.
+    pub fn truncates(&self) -> bool {
+        self.load == LoadMode::Truncate && self.watermark.is_none()
+    }
+
+    /// Whether rows are written as upserts; incremental tables always are.
+    pub fn upserts(&self) -> bool {
+        matches!(self.load, LoadMode::Upsert | LoadMode::DeleteMissing) || self.watermark.is_some()
+    }
+}
+
+/// What happens to the rows a target table already holds.
+#[derive(Debug, Deserialize, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "kebab-case")]
+pub enum LoadMode {
+    /// Empty the table, then insert the source rows.
+    #[default]
+    Truncate,
+    /// Insert the source rows next to the existing ones.
+    Append,
+    /// Insert new rows and update the ones whose key is already there.
+    Upsert,
+    /// Upsert, then delete the rows whose key is not in the source.
+    DeleteMissing,
+}
+
+impl fmt::Display for LoadMode {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::Truncate => write!(f, "truncate"),
+            Self::Append => write!(f, "append"),
+            Self::Upsert => write!(f, "upsert"),
+            Self::DeleteMissing => write!(f, "delete-missing"),
+        }
+    }
+}
+
+/// How table rows are moved to the target.
+#[derive(Debug, Deserialize, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "kebab-case")]
+pub enum TransferMode {
+    /// Batched multi-row INSERTs.
+    #[default]
+    Insert,
+    /// Postgres `COPY ... (FORMAT binary)`, falling back to CSV if the target rejects it.
+    Copy,
+    /// Postgres `COPY ... (FORMAT csv)`.
+    CopyCsv,
+}
+
+#[derive(Deserialize, Clone)]
 pub struct DbConfig {
+    /// Full connection URL; any structured field left empty is filled from it.
+    pub url: Option<String>,
+    #[serde(default)]
     pub username: String,
+    #[serde(default)]
     pub password: String,
+    pub password_file: Option<String>,
+    #[serde(default)]
     pub host: String,
+    #[serde(default)]
     pub port: String,
+    #[serde(default)]
     pub database: String,
     pub schema: Option<String>,
+    /// disable | allow | prefer | require | verify-ca | verify-full
+    pub sslmode: Option<String>,
+    pub ssl_root_cert: Option<String>,
+    pub ssl_cert: Option<String>,
+    pub ssl_key: Option<String>,
+    /// Password of the PKCS#12 bundle in `ssl_cert` (mysql only).
+    pub ssl_pkcs12_password: Option<String>,
+    /// Unix socket path (directory for postgres, socket file for mysql).
+    pub socket: Option<String>,
+    pub application_name: Option<String>,
+    /// Extra engine connection parameters, passed through as-is.
+    #[serde(default)]
+    pub params: BTreeMap<String, String>,
+    /// Most connections kept open to this database at once (default 10).
+    pub pool_size: Option<u32>,
+    /// Open sessions read-only; set for `--dry-run`, never read from the file.
+    #[serde(skip)]
+    pub read_only: bool,
+}
+
+impl DbConfig {
+    pub fn max_connections(&self) -> u32 {
+        self.pool_size.unwrap_or(DEFAULT_POOL_SIZE).max(1)
+    }
+
+    pub(crate) fn apply_url(&mut self) -> CustomResult<()> {
+        let Some(raw_url) = &self.url else {
+            return Ok(());
+        };
+
+        let url = Url::parse(raw_url)
+            .map_err(|err| CustomError::Config(format!("Invalid connection url: {}", err)))?;
+
+        if self.username.is_empty() {
+            self.username = decode(url.username())?;
+        }
+        if self.password.is_empty() {
+            if let Some(password) = url.password() {
+                self.password = decode(password)?;
+            }
+        }
+        if self.host.is_empty() {
+            if let Some(host) = url.host_str() {
+                self.host = decode(host)?;
+            }
+        }
+        if self.port.is_empty() {
+            if let Some(port) = url.port() {
+                self.port = port.to_string();
+            }
+        }
+        if self.database.is_empty() {
+            self.database = decode(url.path().trim_start_matches('/'))?;
+        }
+
+        for (key, value) in url.query_pairs() {
+            let value = value.into_owned();
+            let field = match key.as_ref() {
+                "sslmode" | "ssl-mode" => &mut self.sslmode,
+                "sslrootcert" | "ssl-root-cert" | "ssl-ca" => &mut self.ssl_root_cert,
+                "sslcert" | "ssl-cert" => &mut self.ssl_cert,
+                "sslkey" | "ssl-key" => &mut self.ssl_key,
+                "socket" => &mut self.socket,
+                "host" if value.starts_with('/') => &mut self.socket,
+                "application_name" => &mut self.application_name,
+                _ => {
+                    self.params.entry(key.into_owned()).or_insert(value);
+                    continue;
+                }
+            };
+            field.get_or_insert(value);
+        }
+
+        Ok(())
+    }
+}
+
+fn decode(value: &str) -> CustomResult<String> {
+    percent_decode_str(value)
+        .decode_utf8()
+        .map(|decoded| decoded.into_owned())
+        .map_err(|err| {
+            CustomError::Config(format!(
+                "Invalid percent-encoding in connection url: {}",
+                err
+            ))
+        })
+}
+
+fn redact_url(raw_url: &str) -> String {
+    match Url::parse(raw_url) {
+        Ok(mut url) if url.password().is_some() => {
+            let _ = url.set_password(Some(REDACTED));
+            url.to_string()
+        }
+        Ok(url) => url.to_string(),
+        Err(_) => REDACTED.to_string(),
+    }
+}
+
+impl fmt::Debug for DbConfig {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let password = if self.password.is_empty() {
+            ""
+        } else {
+            REDACTED
+        };
+
+        f.debug_struct("DbConfig")
+            .field("url", &self.url.as_deref().map(redact_url))
+            .field("username", &self.username)
+            .field("password", &password)
+            .field("password_file", &self.password_file)
+            .field("host", &self.host)
+            .field("port", &self.port)
+            .field("database", &self.database)
+            .field("schema", &self.schema)
+            .field("sslmode", &self.sslmode)
+            .field("ssl_root_cert", &self.ssl_root_cert)
+            .field("ssl_cert", &self.ssl_cert)
+            .field("ssl_key", &self.ssl_key)
+            .field(
+                "ssl_pkcs12_password",
+                &self.ssl_pkcs12_password.as_ref().map(|_| REDACTED),
+            )
+            .field("socket", &self.socket)
+            .field("application_name", &self.application_name)
+            .field("params", &self.params)
+            .field("pool_size", &self.pool_size)
+            .field("read_only", &self.read_only)
+            .finish()
+    }
+}
+
+#[derive(Debug, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum Engine {
+    Mysql,
+    Postgres,
+}
+
+impl fmt::Display for Engine {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::Mysql => write!(f, "mysql"),
+            Self::Postgres => write!(f, "postgres"),
+        }
+    }
 }
 
 #[derive(Debug, Deserialize, Clone)]
 pub struct DbTechnology {
-    pub category: String,
+    pub category: Engine,
     pub use_pg_dump: bool,
     pub copy_staging_tables: Option<bool>,
     pub copy_structure: bool,
     pub copy_data: bool,
+    /// Rows per INSERT; also bounds how many rows are held in memory per table.
+    #[serde(default = "default_batch_size")]
+    pub batch_size: usize,
+    /// Tables copied at the same time; 1 copies them one after another.
+    #[serde(default = "default_parallelism")]
+    pub parallelism: usize,
+    /// Extra attempts for a chunk whose copy failed and was rolled back.
+    #[serde(default = "default_chunk_retries")]
+    pub chunk_retries: usize,
+    /// Read every table from one snapshot of the source, so rows copied at different
+    /// times still reference each other.
+    #[serde(default = "default_consistent_snapshot")]
+    pub consistent_snapshot: bool,
+    #[serde(default)]
+    pub structure_mode: StructureMode,
+}
+
+/// How `copy_structure` brings the target schema in line with the source.
+#[derive(Debug, Deserialize, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "kebab-case")]
+pub enum StructureMode {
+    /// Drop the target schema (postgres) or tables (mysql) and create them again.
+    #[default]
+    Recreate,
+    /// Only run the statements that turn the target into the source, keeping its
+    /// target-only tables and the rows of existing tables.
+    Sync,
+}
+
+fn default_batch_size() -> usize {
+    1000
+}
+
+fn default_parallelism() -> usize {
+    1
 }
+
+fn default_chunk_retries() -> usize {
+    2
+}
+
+fn default_consistent_snapshot() -> bool {
+    true
+}
+
 #[derive(Debug, Deserialize, Clone)]
 pub struct LogsConfig {
     pub log_level: LogLevel,
 }
 
+/// Guards against copying into the wrong database, see `safety::check`.
+#[derive(Debug, Deserialize, Clone, Default)]
+pub struct SafetyConfig {
+    /// When non-empty, the target must match one of these `host` / `host/database` entries.
+    #[serde(default)]
+    pub allowed_targets: Vec<String>,
+    /// The target must match none of these; checked before `allowed_targets`.
+    #[serde(default)]
+    pub denied_targets: Vec<String>,
+}
+
+/// How copied tables are compared with the source, see `verify`.
+#[derive(Debug, Deserialize, Clone)]
+#[serde(default)]
+pub struct VerifyConfig {
+    /// Compare a checksum of every row on top of the row counts.
+    pub checksums: bool,
+    /// Primary keys listed per mismatched table; 0 lists none.
+    pub sample_keys: usize,
+}
+
+impl Default for VerifyConfig {
+    fn default() -> Self {
+        Self {
+            checksums: true,
+            sample_keys: 0,
+        }
+    }
+}
+
+/// Options taken from the command line rather than the config file.
+#[derive(Debug, Clone, Default)]
+pub struct RunOptions {
+    pub fail_fast: bool,
+    pub dry_run: bool,
+    pub assume_yes: bool,
+    /// Verify the copied tables once the data step is done.
+    pub verify: bool,
+}
+
 // Top level struct to hold the TOML data.
 #[derive(Debug, Deserialize, Clone)]
 pub struct Config {
@@ -43,17 +373,98 @@ pub struct Config {
     pub tables: TablesConfig,
     pub technology: DbTechnology,
     pub log: LogsConfig,
+    #[serde(default)]
+    pub safety: SafetyConfig,
+    #[serde(default)]
+    pub verify: VerifyConfig,
+    #[serde(skip)]
+    pub run: RunOptions,
+}
+
+impl Config {
+    /// Tables processed at once: `parallelism`, capped by the smaller pool since
+    /// every worker holds a source and a target connection.
+    pub fn workers(&self) -> usize {
+        let pool_size = self
+            .source
+            .max_connections()
+            .min(self.target.max_connections());
+        self.technology.parallelism.clamp(1, pool_size as usize)
+    }
 }
 
-pub fn read_config() -> Config {
-    println!("Reading config file: {}", CONFIG_FILE);
-    let contents = fs::read_to_string(CONFIG_FILE)
-        .expect(format!("Could not read file `{}`", CONFIG_FILE).as_str());
+pub fn read_config(path: &str, profile: Option<&str>) -> CustomResult<Config> {
+    println!("Reading config file: {}", path);
+    let contents = fs::read_to_string(path)
+        .map_err(|err| CustomError::Config(format!("Could not read file `{}`: {}", path, err)))?;
 
-    let data: Config = toml::from_str(&contents)
-        .expect(format!("Unable to load data from `{}`", CONFIG_FILE).as_str());
-    println!("Read config file: {}", CONFIG_FILE);
+    let mut raw: toml::Table = toml::from_str(&contents)
+        .map_err(|err| CustomError::Config(format!("Unable to parse `{}`: {}", path, err)))?;
+
+    let profiles = raw.remove(PROFILES_KEY);
+    if let Some(name) = profile {
+        let profile_table = select_profile(profiles, name)?;
+        merge_tables(&mut raw, profile_table);
+        println!("Applied config profile: {}", name);
+    }
+
+    let mut data: Config = toml::Value::Table(raw).try_into().map_err(|err| {
+        CustomError::Config(format!("Unable to load data from `{}`: {}", path, err))
+    })?;
+    credentials::resolve(&mut data.source, data.technology.category)?;
+    credentials::resolve(&mut data.target, data.technology.category)?;
+    println!("Read config file: {}", path);
     println!("{:#?}", data);
+    if data.workers() < data.technology.parallelism {
+        println!(
+            "parallelism {} exceeds the connection pool size, using {} workers",
+            data.technology.parallelism,
+            data.workers()
+        );
+    }
+
+    Ok(data)
+}
+
+fn select_profile(profiles: Option<toml::Value>, name: &str) -> CustomResult<toml::Table> {
+    let mut profiles = match profiles {
+        Some(toml::Value::Table(profiles)) => profiles,
+        Some(_) => {
+            return Err(CustomError::Config(format!(
+                "`{}` must be a table of named profiles",
+                PROFILES_KEY
+            )));
+        }
+        None => toml::Table::new(),
+    };
+
+    match profiles.remove(name) {
+        Some(toml::Value::Table(profile)) => Ok(profile),
+        Some(_) => Err(CustomError::Config(format!(
+            "Profile `{}` must be a table",
+            name
+        ))),
+        None => {
+            let available: Vec<&String> = profiles.keys().collect();
+            Err(CustomError::Config(format!(
+                "Profile `{}` not found. Available profiles: {:?}",
+                name, available
+            )))
+        }
+    }
+}
 
-    data
+// Profile values win over the base section; nested tables are merged key by key
+// so a profile only has to list what differs (e.g. just `target.host`).
+fn merge_tables(base: &mut toml::Table, overrides: toml::Table) {
+    for (key, value) in overrides {
+        match (base.get_mut(&key), value) {
+            (Some(toml::Value::Table(base_table)), toml::Value::Table(override_table)) => {
+                merge_tables(base_table, override_table);
+            }
+            (_, value) => {
+                base.insert(key, value);
+            }
+        }
+    }
 }
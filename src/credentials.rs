@@ -0,0 +1,178 @@
+use std::env;
+use std::fs;
+use std::path::PathBuf;
+
+use crate::config::{DbConfig, Engine};
+use crate::error::{CustomError, CustomResult};
+
+const PGPASS_ENV: &str = "PGPASSFILE";
+const PGPASS_FILE: &str = ".pgpass";
+const MYCNF_FILE: &str = ".my.cnf";
+const DEFAULT_PG_PORT: &str = "5432";
+
+// Resolves `${VAR}` placeholders and fills the password from `password_file`,
+// `~/.pgpass` or `~/.my.cnf`, in that order, when it is not set inline.
+pub fn resolve(db_config: &mut DbConfig, category: Engine) -> CustomResult<()> {
+    if let Some(url) = &db_config.url {
+        db_config.url = Some(interpolate(url)?);
+        db_config.apply_url()?;
+    }
+    db_config.username = interpolate(&db_config.username)?;
+    db_config.host = interpolate(&db_config.host)?;
+    db_config.port = interpolate(&db_config.port)?;
+    db_config.database = interpolate(&db_config.database)?;
+    if let Some(schema) = &db_config.schema {
+        db_config.schema = Some(interpolate(schema)?);
+    }
+    if let Some(password) = &db_config.ssl_pkcs12_password {
+        db_config.ssl_pkcs12_password = Some(interpolate(password)?);
+    }
+
+    if !db_config.password.is_empty() {
+        db_config.password = interpolate(&db_config.password)?;
+        return Ok(());
+    }
+
+    if let Some(password_file) = &db_config.password_file {
+        let path = interpolate(password_file)?;
+        db_config.password = read_password_file(&path)?;
+        return Ok(());
+    }
+
+    let password = match category {
+        Engine::Postgres => lookup_pgpass(db_config),
+        Engine::Mysql => lookup_mycnf(),
+    };
+
+    match password {
+        Some(password) => {
+            db_config.password = password;
+            Ok(())
+        }
+        None => {
+            println!(
+                "No password configured for {}@{}/{}; connecting without one",
+                db_config.username, db_config.host, db_config.database
+            );
+            Ok(())
+        }
+    }
+}
+
+fn interpolate(value: &str) -> CustomResult<String> {
+    let mut result = String::with_capacity(value.len());
+    let mut rest = value;
+
+    while let Some(start) = rest.find("${") {
+        result.push_str(&rest[..start]);
+        let after = &rest[start + 2..];
+        let end = after
+            .find('}')
+            .ok_or_else(|| CustomError::Config("Unterminated `${` in config value".to_string()))?;
+        let name = &after[..end];
+        let resolved = env::var(name).map_err(|_| {
+            CustomError::Config(format!("Environment variable `{}` is not set", name))
+        })?;
+        result.push_str(&resolved);
+        rest = &after[end + 1..];
+    }
+    result.push_str(rest);
+
+    Ok(result)
+}
+
+fn read_password_file(path: &str) -> CustomResult<String> {
+    let contents = fs::read_to_string(path).map_err(|err| {
+        CustomError::Config(format!("Could not read password file `{}`: {}", path, err))
+    })?;
+
+    Ok(contents.trim_end_matches(['\r', '\n']).to_string())
+}
+
+fn home_file(name: &str) -> Option<PathBuf> {
+    env::var_os("HOME").map(|home| PathBuf::from(home).join(name))
+}
+
+fn lookup_pgpass(db_config: &DbConfig) -> Option<String> {
+    let path = env::var_os(PGPASS_ENV)
+        .map(PathBuf::from)
+        .or_else(|| home_file(PGPASS_FILE))?;
+    let contents = fs::read_to_string(path).ok()?;
+
+    let port = if db_config.port.is_empty() {
+        DEFAULT_PG_PORT
+    } else {
+        db_config.port.as_str()
+    };
+    let wanted = [
+        db_config.host.as_str(),
+        port,
+        db_config.database.as_str(),
+        db_config.username.as_str(),
+    ];
+
+    contents
+        .lines()
+        .filter(|line| !line.trim_start().starts_with('#'))
+        .map(split_pgpass_line)
+        .find(|fields| {
+            fields.len() == 5
+                && fields[..4]
+                    .iter()
+                    .zip(wanted.iter())
+                    .all(|(field, value)| field == "*" || field == value)
+        })
+        .map(|mut fields| fields.remove(4))
+}
+
+// Splits a `host:port:database:username:password` line, honouring `\:` and `\\`.
+fn split_pgpass_line(line: &str) -> Vec<String> {
+    let mut fields = vec![String::new()];
+    let mut chars = line.chars();
+
+    while let Some(ch) = chars.next() {
+        match ch {
+            '\\' => {
+                if let Some(escaped) = chars.next() {
+                    fields.last_mut().unwrap().push(escaped);
+                }
+            }
+            ':' => fields.push(String::new()),
+            _ => fields.last_mut().unwrap().push(ch),
+        }
+    }
+
+    fields
+}
+
+fn lookup_mycnf() -> Option<String> {
+    let contents = fs::read_to_string(home_file(MYCNF_FILE)?).ok()?;
+    let mut in_client_section = false;
+
+    for line in contents.lines() {
+        let line = line.trim();
+        if line.starts_with('[') && line.ends_with(']') {
+            let section = &line[1..line.len() - 1];
+            in_client_section = section == "client" || section == "mysql";
+            continue;
+        }
+
+        if !in_client_section {
+            continue;
+        }
+
+        if let Some((key, value)) = line.split_once('=') {
+            if key.trim() == "password" {
+                let value = value.trim();
+                let value = value
+                    .strip_prefix('"')
+                    .and_then(|v| v.strip_suffix('"'))
+                    .or_else(|| value.strip_prefix('\'').and_then(|v| v.strip_suffix('\'')))
+                    .unwrap_or(value);
+                return Some(value.to_string());
+            }
+        }
+    }
+
+    None
+}
@@ -0,0 +1,190 @@
+use std::collections::{BTreeMap, BTreeSet};
+
+/// Tables in foreign key order: every table comes after the tables it references.
+#[derive(Debug, Default)]
+pub struct LoadOrder {
+    pub tables: Vec<String>,
+    /// `tables` split into groups that only reference earlier groups, so the tables
+    /// of one group can be loaded at the same time. Cyclic leftovers come last.
+    pub levels: Vec<Vec<String>>,
+    /// Tables in a reference cycle (including self-references) or depending on one.
+    /// No order satisfies their constraints, so they are loaded with checks disabled.
+    pub cyclic: BTreeSet<String>,
+    /// `(child, parent)` references between the listed tables.
+    pub references: Vec<(String, String)>,
+}
+
+impl LoadOrder {
+    /// Tables referencing any of `tables`, directly or through other tables.
+    pub fn dependents(&self, tables: &BTreeSet<String>) -> BTreeSet<String> {
+        let mut found = BTreeSet::new();
+        let mut queue: Vec<&String> = tables.iter().collect();
+        while let Some(parent) = queue.pop() {
+            for (child, _) in self.references.iter().filter(|(_, p)| p == parent) {
+                if !tables.contains(child) && found.insert(child.clone()) {
+                    queue.push(child);
+                }
+            }
+        }
+        found
+    }
+}
+
+/// Sorts `tables` by `(child, parent)` foreign key references with Kahn's algorithm.
+/// References to tables outside the list are ignored and ties keep the given order.
+pub fn sort_tables(tables: &[String], references: &[(String, String)]) -> LoadOrder {
+    let position: BTreeMap<&str, usize> = tables
+        .iter()
+        .enumerate()
+        .map(|(index, table)| (table.as_str(), index))
+        .collect();
+
+    let mut in_degree = vec![0; tables.len()];
+    let mut children: Vec<BTreeSet<usize>> = vec![BTreeSet::new(); tables.len()];
+    let mut cyclic = BTreeSet::new();
+    let mut listed = Vec::new();
+
+    for (child, parent) in references {
+        let (Some(&child_index), Some(&parent_index)) =
+            (position.get(child.as_str()), position.get(parent.as_str()))
+        else {
+            continue;
+        };
+        listed.push((child.clone(), parent.clone()));
+
+        if child_index == parent_index {
+            // Rows of a self-referencing table can arrive in any order
+            cyclic.insert(child.clone());
+        } else if children[parent_index].insert(child_index) {
+            in_degree[child_index] += 1;
+        }
+    }
+
+    let mut ready: Vec<usize> = (0..tables.len())
+        .filter(|index| in_degree[*index] == 0)
+        .collect();
+    let mut levels = Vec::new();
+    while !ready.is_empty() {
+        let mut next = BTreeSet::new();
+        for &index in &ready {
+            for &child in &children[index] {
+                in_degree[child] -= 1;
+                if in_degree[child] == 0 {
+                    next.insert(child);
+                }
+            }
+        }
+        levels.push(ready.iter().map(|index| tables[*index].clone()).collect());
+        ready = next.into_iter().collect();
+    }
+
+    // Whatever is left still waits on a table in a cycle
+    let leftover: Vec<String> = tables
+        .iter()
+        .enumerate()
+        .filter(|(index, _)| in_degree[*index] > 0)
+        .map(|(_, table)| table.clone())
+        .collect();
+    if !leftover.is_empty() {
+        cyclic.extend(leftover.iter().cloned());
+        levels.push(leftover);
+    }
+
+    LoadOrder {
+        tables: levels.concat(),
+        levels,
+        cyclic,
+        references: listed,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeSet;
+
+    use super::sort_tables;
+
+    fn names(tables: &[&str]) -> Vec<String> {
+        tables.iter().map(|table| table.to_string()).collect()
+    }
+
+    fn references(pairs: &[(&str, &str)]) -> Vec<(String, String)> {
+        pairs
+            .iter()
+            .map(|(child, parent)| (child.to_string(), parent.to_string()))
+            .collect()
+    }
+
+    fn set(tables: &[&str]) -> BTreeSet<String> {
+        names(tables).into_iter().collect()
+    }
+
+    #[test]
+    fn chain_loads_parents_first() {
+        let order = sort_tables(
+            &names(&["c", "b", "a"]),
+            &references(&[("b", "a"), ("c", "b")]),
+        );
+
+        assert_eq!(order.tables, names(&["a", "b", "c"]));
+        assert_eq!(
+            order.levels,
+            vec![names(&["a"]), names(&["b"]), names(&["c"])]
+        );
+        assert!(order.cyclic.is_empty());
+        assert_eq!(order.dependents(&set(&["a"])), set(&["b", "c"]));
+    }
+
+    #[test]
+    fn diamond_loads_siblings_together_in_given_order() {
+        let order = sort_tables(
+            &names(&["d", "c", "b", "a"]),
+            &references(&[("b", "a"), ("c", "a"), ("d", "b"), ("d", "c")]),
+        );
+
+        assert_eq!(
+            order.levels,
+            vec![names(&["a"]), names(&["c", "b"]), names(&["d"])]
+        );
+        assert!(order.cyclic.is_empty());
+        assert_eq!(order.dependents(&set(&["b"])), set(&["d"]));
+    }
+
+    #[test]
+    fn two_cycle_and_its_dependents_load_last() {
+        let order = sort_tables(
+            &names(&["a", "b", "c", "x"]),
+            &references(&[("a", "b"), ("b", "a"), ("c", "a")]),
+        );
+
+        assert_eq!(order.levels, vec![names(&["x"]), names(&["a", "b", "c"])]);
+        assert_eq!(order.tables, names(&["x", "a", "b", "c"]));
+        assert_eq!(order.cyclic, set(&["a", "b", "c"]));
+    }
+
+    #[test]
+    fn self_reference_is_cyclic_but_keeps_its_level() {
+        let order = sort_tables(
+            &names(&["employees", "departments"]),
+            &references(&[("employees", "employees"), ("employees", "departments")]),
+        );
+
+        assert_eq!(
+            order.levels,
+            vec![names(&["departments"]), names(&["employees"])]
+        );
+        assert_eq!(order.cyclic, set(&["employees"]));
+    }
+
+    #[test]
+    fn references_outside_the_list_are_ignored() {
+        let order = sort_tables(
+            &names(&["b", "a"]),
+            &references(&[("a", "missing"), ("missing", "b")]),
+        );
+
+        assert_eq!(order.levels, vec![names(&["b", "a"])]);
+        assert!(order.references.is_empty());
+        assert!(order.cyclic.is_empty());
+    }
+}
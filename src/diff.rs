@@ -0,0 +1,208 @@
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt::Write;
+
+use clap::ValueEnum;
+use serde_derive::Serialize;
+
+use crate::error::{CustomError, CustomResult};
+
+/// How the `diff` subcommand prints its result.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum DiffFormat {
+    Text,
+    Json,
+}
+
+/// Catalog of one side, every definition with its schema name stripped so both
+/// sides compare as text.
+#[derive(Debug, Default)]
+pub struct Schema {
+    pub tables: BTreeMap<String, TableSchema>,
+    /// Enum name -> comma separated labels in sort order.
+    pub enums: BTreeMap<String, String>,
+    /// Sequence name -> definition.
+    pub sequences: BTreeMap<String, String>,
+}
+
+/// Name -> definition of everything a table is made of.
+#[derive(Debug, Default)]
+pub struct TableSchema {
+    pub columns: BTreeMap<String, String>,
+    pub indexes: BTreeMap<String, String>,
+    pub constraints: BTreeMap<String, String>,
+}
+
+/// One object that differs. `missing` objects are only in the source and would be
+/// created by a copy, `extra` ones only in the target.
+#[derive(Debug, Serialize, PartialEq, Eq)]
+#[serde(tag = "change", rename_all = "snake_case")]
+pub enum Change {
+    Missing {
+        name: String,
+        source: String,
+    },
+    Extra {
+        name: String,
+        target: String,
+    },
+    Changed {
+        name: String,
+        source: String,
+        target: String,
+    },
+}
+
+#[derive(Debug, Serialize)]
+pub struct TableDiff {
+    pub table: String,
+    /// `missing` / `extra` when the table is on one side only, `changed` otherwise.
+    pub status: String,
+    pub columns: Vec<Change>,
+    pub indexes: Vec<Change>,
+    pub constraints: Vec<Change>,
+}
+
+#[derive(Debug, Serialize, Default)]
+pub struct SchemaDiff {
+    pub tables: Vec<TableDiff>,
+    pub enums: Vec<Change>,
+    pub sequences: Vec<Change>,
+}
+
+impl SchemaDiff {
+    pub fn is_empty(&self) -> bool {
+        self.tables.is_empty() && self.enums.is_empty() && self.sequences.is_empty()
+    }
+}
+
+/// Removes `schema.` and `"schema".` qualifiers from a definition.
+pub fn strip_schema(definition: &str, schema: &str) -> String {
+    definition
+        .replace(&format!("\"{}\".", schema), "")
+        .replace(&format!("{}.", schema), "")
+}
+
+pub fn compare(source: &Schema, target: &Schema) -> SchemaDiff {
+    let empty = TableSchema::default();
+    let names: BTreeSet<&String> = source.tables.keys().chain(target.tables.keys()).collect();
+
+    let mut tables = Vec::new();
+    for name in names {
+        let (source_table, target_table) = (source.tables.get(name), target.tables.get(name));
+        let status = match (source_table, target_table) {
+            (Some(_), None) => "missing",
+            (None, Some(_)) => "extra",
+            _ => "changed",
+        };
+        let source_table = source_table.unwrap_or(&empty);
+        let target_table = target_table.unwrap_or(&empty);
+
+        let table = TableDiff {
+            table: name.to_string(),
+            status: status.to_string(),
+            columns: compare_items(&source_table.columns, &target_table.columns),
+            indexes: compare_items(&source_table.indexes, &target_table.indexes),
+            constraints: compare_items(&source_table.constraints, &target_table.constraints),
+        };
+        if status != "changed"
+            || !(table.columns.is_empty()
+                && table.indexes.is_empty()
+                && table.constraints.is_empty())
+        {
+            tables.push(table);
+        }
+    }
+
+    SchemaDiff {
+        tables,
+        enums: compare_items(&source.enums, &target.enums),
+        sequences: compare_items(&source.sequences, &target.sequences),
+    }
+}
+
+fn compare_items(
+    source: &BTreeMap<String, String>,
+    target: &BTreeMap<String, String>,
+) -> Vec<Change> {
+    let names: BTreeSet<&String> = source.keys().chain(target.keys()).collect();
+    names
+        .into_iter()
+        .filter_map(|name| match (source.get(name), target.get(name)) {
+            (Some(source), None) => Some(Change::Missing {
+                name: name.to_string(),
+                source: source.to_string(),
+            }),
+            (None, Some(target)) => Some(Change::Extra {
+                name: name.to_string(),
+                target: target.to_string(),
+            }),
+            (Some(source), Some(target)) if source != target => Some(Change::Changed {
+                name: name.to_string(),
+                source: source.to_string(),
+                target: target.to_string(),
+            }),
+            _ => None,
+        })
+        .collect()
+}
+
+/// Prints the diff to stdout in the requested format.
+pub fn print(diff: &SchemaDiff, format: DiffFormat) -> CustomResult<()> {
+    let output = match format {
+        DiffFormat::Json => serde_json::to_string_pretty(diff)
+            .map_err(|err| CustomError::Config(format!("Failed to encode diff: {}", err)))?,
+        DiffFormat::Text => render_text(diff),
+    };
+    println!("{}", output);
+    Ok(())
+}
+
+fn render_text(diff: &SchemaDiff) -> String {
+    if diff.is_empty() {
+        return "Source and target schemas match".to_string();
+    }
+
+    let mut text = String::new();
+    for table in &diff.tables {
+        match table.status.as_str() {
+            "missing" => writeln!(text, "table {}: missing in target", table.table),
+            "extra" => writeln!(text, "table {}: only in target", table.table),
+            _ => writeln!(text, "table {}:", table.table),
+        }
+        .unwrap();
+        // A table on one side only is listed without its parts
+        if table.status != "changed" {
+            continue;
+        }
+        render_changes(&mut text, "  column", &table.columns);
+        render_changes(&mut text, "  index", &table.indexes);
+        render_changes(&mut text, "  constraint", &table.constraints);
+    }
+    render_changes(&mut text, "enum", &diff.enums);
+    render_changes(&mut text, "sequence", &diff.sequences);
+
+    text.trim_end().to_string()
+}
+
+fn render_changes(text: &mut String, kind: &str, changes: &[Change]) {
+    for change in changes {
+        match change {
+            Change::Missing { name, source } => {
+                writeln!(text, "{} {}: missing in target: {}", kind, name, source)
+            }
+            Change::Extra { name, target } => {
+                writeln!(text, "{} {}: only in target: {}", kind, name, target)
+            }
+            Change::Changed {
+                name,
+                source,
+                target,
+            } => writeln!(
+                text,
+                "{} {}: source: {} / target: {}",
+                kind, name, source, target
+            ),
+        }
+        .unwrap();
+    }
+}
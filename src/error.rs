@@ -1,16 +1,232 @@
+use std::fmt;
+
 pub type CustomResult<T> = core::result::Result<T, CustomError>;
 
+/// Step of the migration an error happened in.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Phase {
+    Schema,
+    Enum,
+    Structure,
+    Partition,
+    Data,
+    Sequence,
+    Index,
+    Constraint,
+    Verify,
+}
+
+impl fmt::Display for Phase {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::Schema => write!(f, "schema"),
+            Self::Enum => write!(f, "enum"),
+            Self::Structure => write!(f, "structure"),
+            Self::Partition => write!(f, "partition"),
+            Self::Data => write!(f, "data"),
+            Self::Sequence => write!(f, "sequence"),
+            Self::Index => write!(f, "index"),
+            Self::Constraint => write!(f, "constraint"),
+            Self::Verify => write!(f, "verify"),
+        }
+    }
+}
+
+/// Underlying driver error.
+#[derive(Debug)]
+pub enum DbError {
+    Postgres(sqlx::Error),
+    Mysql(mysql::Error),
+}
+
+impl fmt::Display for DbError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::Postgres(err) => write!(f, "{}", err),
+            Self::Mysql(err) => write!(f, "{}", err),
+        }
+    }
+}
+
+impl std::error::Error for DbError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Self::Postgres(err) => Some(err),
+            Self::Mysql(err) => Some(err),
+        }
+    }
+}
+
+impl From<sqlx::Error> for DbError {
+    fn from(err: sqlx::Error) -> Self {
+        Self::Postgres(err)
+    }
+}
+
+impl From<mysql::Error> for DbError {
+    fn from(err: mysql::Error) -> Self {
+        Self::Mysql(err)
+    }
+}
+
 #[derive(Debug)]
 pub enum CustomError {
-    QueryExecution,
-    DbTableStructure,
-    DbConnection,
-    CommandExecution,
+    Config(String),
+    UnsupportedEngine(String),
+    SafetyCheck(String),
+    QueryExecution {
+        phase: Phase,
+        table: Option<String>,
+        statement: String,
+        source: DbError,
+    },
+    DbTableStructure {
+        phase: Phase,
+        table: String,
+        message: String,
+    },
+    DbConnection {
+        source: DbError,
+    },
+    CommandExecution {
+        command: String,
+        message: String,
+        source: Option<std::io::Error>,
+    },
+    TablesFailed {
+        phase: Phase,
+        failed: Vec<String>,
+        total: usize,
+    },
+}
+
+/// Process exit codes, see README.
+pub const EXIT_FAILURE: u8 = 1;
+pub const EXIT_CONNECTION_FAILURE: u8 = 2;
+pub const EXIT_PARTIAL_FAILURE: u8 = 3;
+pub const EXIT_TOTAL_FAILURE: u8 = 4;
+
+impl CustomError {
+    pub fn query(phase: Phase, statement: impl Into<String>, source: impl Into<DbError>) -> Self {
+        Self::QueryExecution {
+            phase,
+            table: None,
+            statement: statement.into(),
+            source: source.into(),
+        }
+    }
+
+    pub fn structure(phase: Phase, table: impl Into<String>, message: impl Into<String>) -> Self {
+        Self::DbTableStructure {
+            phase,
+            table: table.into(),
+            message: message.into(),
+        }
+    }
+
+    pub fn connection(source: impl Into<DbError>) -> Self {
+        Self::DbConnection {
+            source: source.into(),
+        }
+    }
+
+    pub fn phase(&self) -> Option<Phase> {
+        match self {
+            Self::QueryExecution { phase, .. }
+            | Self::DbTableStructure { phase, .. }
+            | Self::TablesFailed { phase, .. } => Some(*phase),
+            _ => None,
+        }
+    }
+
+    pub fn exit_code(&self) -> u8 {
+        match self {
+            Self::DbConnection { .. } => EXIT_CONNECTION_FAILURE,
+            Self::TablesFailed { failed, total, .. } if failed.len() < *total => {
+                EXIT_PARTIAL_FAILURE
+            }
+            Self::TablesFailed { .. } => EXIT_TOTAL_FAILURE,
+            _ => EXIT_FAILURE,
+        }
+    }
+
+    /// Attaches the table the failed statement belongs to, if not already set.
+    pub fn with_table(self, table: &str) -> Self {
+        match self {
+            Self::QueryExecution {
+                phase,
+                table: None,
+                statement,
+                source,
+            } => Self::QueryExecution {
+                phase,
+                table: Some(table.to_string()),
+                statement,
+                source,
+            },
+            other => other,
+        }
+    }
+}
+
+impl std::error::Error for CustomError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Self::QueryExecution { source, .. } => Some(source),
+            Self::DbConnection { source } => Some(source),
+            Self::CommandExecution {
+                source: Some(source),
+                ..
+            } => Some(source),
+            _ => None,
+        }
+    }
 }
 
-impl std::error::Error for CustomError {}
 impl core::fmt::Display for CustomError {
     fn fmt(&self, f: &mut core::fmt::Formatter) -> core::result::Result<(), core::fmt::Error> {
-        write!(f, "{self:?}")
+        match self {
+            Self::Config(message) => write!(f, "config error: {}", message),
+            Self::UnsupportedEngine(message) => write!(f, "unsupported engine: {}", message),
+            Self::SafetyCheck(message) => write!(f, "refusing to run: {}", message),
+            Self::QueryExecution {
+                phase,
+                table,
+                statement,
+                source,
+            } => {
+                write!(f, "[{}]", phase)?;
+                if let Some(table) = table {
+                    write!(f, " table `{}`:", table)?;
+                }
+                write!(
+                    f,
+                    " query failed: {}\n  statement: {}",
+                    source,
+                    statement.trim()
+                )
+            }
+            Self::DbTableStructure {
+                phase,
+                table,
+                message,
+            } => write!(f, "[{}] table `{}`: {}", phase, table, message),
+            Self::DbConnection { source } => write!(f, "connection failed: {}", source),
+            Self::CommandExecution {
+                command, message, ..
+            } => write!(f, "command failed: {}\n  command: {}", message, command),
+            Self::TablesFailed {
+                phase,
+                failed,
+                total,
+            } => write!(
+                f,
+                "[{}] {} of {} tables failed: {}",
+                phase,
+                failed.len(),
+                total,
+                failed.join(", ")
+            ),
+        }
     }
 }
@@ -1,35 +1,77 @@
 use clap::Parser;
+use std::process::ExitCode;
+mod checkpoint;
+mod chunk;
 mod cli;
 mod config;
+mod credentials;
+mod dependency;
+mod diff;
 mod error;
 mod logger;
 mod mysql_processor;
+mod parallel;
+mod plan;
 mod psql_processor;
+mod registry;
+mod report;
+mod safety;
 mod traits;
+mod verify;
+mod watermark;
+use checkpoint::Checkpoint;
 use cli::CLi;
 use error::CustomResult;
 use logger::Logger;
-use mysql_processor::migrator::Migrator as MysqlMigrator;
-use psql_processor::migrator::Migrator as PsqllMigrator;
+use plan::Plan;
+use registry::Task;
+use report::Reporter;
 
 #[tokio::main]
-async fn main() -> CustomResult<()> {
+async fn main() -> ExitCode {
+    let result = run().await;
+    Reporter::new().finish(&result);
+    Plan::new().finish();
+    Checkpoint::new().finish(&result);
+
+    match result {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(err) => {
+            eprintln!("Error: {}", err);
+            ExitCode::from(err.exit_code())
+        }
+    }
+}
+
+async fn run() -> CustomResult<()> {
     println!("Reading cli args...");
     let cli_args = CLi::parse();
     println!("CLI args: {:#?}", cli_args);
-    let config = config::read_config();
-
-    Logger::init(config.log.log_level);
-    if config.technology.category == "mysql" {
-        let migrator = MysqlMigrator { config };
-        migrator.migrate().await?;
-        return Ok(());
+    let mut config = config::read_config(&cli_args.path, cli_args.profile.as_deref())?;
+    config.run = cli_args.run_options();
+    let task = cli_args.task();
+    if task != Task::Migrate {
+        // Nothing is written, so neither the safety checks nor a checkpoint apply
+        config.source.read_only = true;
+        config.target.read_only = true;
     }
-    if config.technology.category == "postgres" {
-        let migrator = PsqllMigrator { config };
-        migrator.migrate().await?;
-        return Ok(());
+    if config.run.dry_run {
+        config.source.read_only = true;
+        config.target.read_only = true;
+        Plan::init(cli_args.plan_file.clone());
     }
 
-    panic!("Not supported technology received. Only mysql is supported. Exiting.");
+    Logger::init(config.log.log_level);
+    Reporter::init(
+        cli_args.report.clone(),
+        &config.technology.category.to_string(),
+    );
+    if task == Task::Migrate {
+        safety::check(&config)?;
+        if let Some(path) = cli_args.checkpoint.clone() {
+            Checkpoint::init(path, cli_args.resume, &config)?;
+        }
+    }
+    let run = registry::get_runner(config.technology.category)?;
+    run(config, task).await
 }
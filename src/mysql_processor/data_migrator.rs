@@ -1,9 +1,42 @@
-use std::collections::HashMap;
+use std::collections::{BTreeMap, BTreeSet};
+use std::time::Instant;
 
-use crate::{config::Config, error::CustomError, mysql_processor::db::get_connection};
-use mysql::{from_value, prelude::Queryable, PooledConn, Row};
+use crate::{
+    checkpoint::Checkpoint,
+    chunk::{split, Chunk},
+    config::{Config, LoadMode, StructureMode},
+    dependency::{sort_tables, LoadOrder},
+    error::{CustomError, Phase},
+    mysql_processor::db::{exec_drop, get_connections_pool, get_pooled_connection},
+    mysql_processor::snapshot::SnapshotConnections,
+    parallel::run_blocking,
+    plan::Plan,
+    report::Reporter,
+    watermark::{above, Increment, STATE_TABLE},
+};
+use mysql::{prelude::Queryable, Params, Pool, PooledConn, Row, TxOpts};
 
 use crate::CustomResult;
+
+const MAX_PLACEHOLDERS: usize = 65_535;
+const INTEGER_TYPES: [&str; 5] = ["tinyint", "smallint", "mediumint", "int", "bigint"];
+/// Temporary table of the target session the source keys of a `delete-missing` table
+/// are staged in.
+const KEYS_TABLE: &str = "db_copy_keys";
+
+/// Unit of work for the load workers: a whole table, or one chunk of it.
+struct Job {
+    table: String,
+    chunk: Option<Chunk>,
+}
+
+/// Rows and bytes written so far, reported once it is known they are kept.
+#[derive(Default)]
+struct Progress {
+    rows: u64,
+    bytes: u64,
+}
+
 pub struct DataMigrator {
     pub config: Config,
 }
@@ -11,72 +44,997 @@ pub struct DataMigrator {
 impl DataMigrator {
     pub fn migrate(&self) -> CustomResult<()> {
         println!("Connecting to source database");
-        let mut source_conn = get_connection(&self.config.source)?;
+        let source_pool = get_connections_pool(&self.config.source)?;
         println!("Connected to source database");
 
         println!("Connecting to target database");
-        let mut target_conn = get_connection(&self.config.target)?;
+        let target_pool = get_connections_pool(&self.config.target)?;
         println!("Connected to target database");
 
-        for table in &self.config.tables.data_source {
-            if !self.config.technology.copy_structure {
+        let order = self.get_load_order(&mut get_pooled_connection(&source_pool)?)?;
+        let workers = self.config.workers();
+        let shared = self.open_snapshot(workers)?;
+        let reporter = Reporter::new();
+        let checkpoint = Checkpoint::new();
+        let copied = checkpoint.prepare_data(&order);
+        let mut failed_tables = Vec::new();
+        // Tables --fail-fast stopped before don't count towards the exit status
+        let mut attempted = BTreeSet::new();
+
+        let incremental = |table: &String| self.config.tables.settings(table).watermark.is_some();
+        if order.tables.iter().any(incremental) {
+            self.create_state_table(&mut get_pooled_connection(&target_pool)?)?;
+        }
+
+        // Children are emptied before their parents and loaded after them. Tables the
+        // structure step created are empty, unless they were kept for --resume or the
+        // sync mode altered them in place.
+        let recreated = self.config.technology.copy_structure
+            && self.config.technology.structure_mode == StructureMode::Recreate;
+        if !recreated || checkpoint.is_resuming() {
+            let mut target_conn = get_pooled_connection(&target_pool)?;
+            for table in order.tables.iter().rev() {
+                if copied.contains(table) || checkpoint.has_copied_chunks(table) {
+                    println!("Keeping rows of table {} copied by an earlier run", table);
+                    continue;
+                }
+                let settings = self.config.tables.settings(table);
+                if !settings.truncates() {
+                    let mode = match settings.watermark {
+                        Some(_) => "incremental".to_string(),
+                        None => settings.load.to_string(),
+                    };
+                    println!("Keeping rows of table {} ({})", table, mode);
+                    continue;
+                }
                 println!("Truncating table: {}", table);
-                self.truncate_table(&mut target_conn, table)?;
-                println!("Truncated table: {}", table);
-            };
+                attempted.insert(table.to_string());
+                let started = Instant::now();
+                let result = self.truncate_table(&mut target_conn, table);
+                reporter.table_phase(table, Phase::Data, started.elapsed());
+                if let Err(err) = result {
+                    self.record_failure(table, &err, &mut failed_tables);
+                    if self.config.run.fail_fast {
+                        break;
+                    }
+                }
+            }
+        }
 
-            println!("Migrating data for table: {}", table);
-            let data: Vec<HashMap<String, mysql::Value>> =
-                self.get_data(&mut source_conn, table)?;
+        println!("Loading tables with {} workers", workers);
 
-            for row in data {
-                let column_names: Vec<String> = row
-                    .iter()
-                    .map(|(key, _)| format!("`{}`", key.as_str()))
-                    .collect();
+        // A level only references earlier levels, so its tables load side by side
+        let total = order.tables.len() - copied.len();
+        let mut finished = 0;
+        for level in &order.levels {
+            if self.config.run.fail_fast && !failed_tables.is_empty() {
+                println!("Stopping after first failure (--fail-fast)");
+                break;
+            }
+            let pending: Vec<String> = level
+                .iter()
+                .filter(|table| !failed_tables.contains(table) && !copied.contains(*table))
+                .cloned()
+                .collect();
+            attempted.extend(pending.iter().cloned());
 
-                let values: Vec<mysql::Value> = row.values().map(|value| value.clone()).collect();
+            // Chunks of a table are separate jobs sharing the same workers
+            let mut jobs = Vec::new();
+            let mut snapshots = BTreeMap::new();
+            for table in &pending {
+                let cyclic = order.cyclic.contains(table);
+                match self.plan_jobs(&source_pool, table, cyclic, shared.as_ref(), &mut snapshots) {
+                    Ok(table_jobs) => jobs.extend(table_jobs),
+                    Err(err) => self.record_failure(table, &err, &mut failed_tables),
+                }
+            }
 
-                let values_as_strings: Vec<String> = values
-                    .iter()
-                    .map(|value| match value {
-                        mysql::Value::NULL => "NULL".to_string(),
-                        _ => {
-                            let mut value = from_value::<String>(value.clone());
-                            if value.contains('\'') {
-                                value = value.replace('\'', "\\'");
-                            }
-                            format!("'{}'", value)
+            let mut remaining: BTreeMap<&str, usize> = BTreeMap::new();
+            for job in &jobs {
+                *remaining.entry(job.table.as_str()).or_default() += 1;
+            }
+            let planned = remaining.clone();
+            let mut errors: BTreeMap<&str, CustomError> = BTreeMap::new();
+
+            run_blocking(
+                &jobs,
+                workers,
+                |job| {
+                    let snapshot = shared.as_ref().or_else(|| snapshots.get(&job.table));
+                    self.run_job(&source_pool, &target_pool, job, &order, snapshot)
+                },
+                |job, result| {
+                    let table = job.table.as_str();
+                    if let Err(err) = result {
+                        errors.entry(table).or_insert(err);
+                    }
+                    let left = remaining.get_mut(table).unwrap();
+                    *left -= 1;
+                    if *left == 0 {
+                        match errors.remove(table) {
+                            None => checkpoint.complete(table, Phase::Data),
+                            Some(err) => self.record_failure(table, &err, &mut failed_tables),
                         }
-                    })
-                    .collect();
+                        finished += 1;
+                        println!("Finished table {} ({}/{})", table, finished, total);
+                    }
+                    !self.config.run.fail_fast || (failed_tables.is_empty() && errors.is_empty())
+                },
+            );
+
+            // --fail-fast can leave tables with only some of their chunks copied
+            for (table, left) in remaining {
+                if left == 0 || (left == planned[table] && !errors.contains_key(table)) {
+                    continue;
+                }
+                let err = errors.remove(table).unwrap_or_else(|| {
+                    CustomError::structure(
+                        Phase::Data,
+                        table,
+                        format!(
+                            "stopped with {} of {} chunks not copied",
+                            left, planned[table]
+                        ),
+                    )
+                });
+                self.record_failure(table, &err, &mut failed_tables);
+            }
+        }
 
-                let insert_query = format!(
-                    "INSERT INTO {} ({}) VALUES ({});",
+        // Children lose their rows before the parents they reference
+        let mut target_conn = get_pooled_connection(&target_pool)?;
+        for table in order.tables.iter().rev() {
+            if self.config.run.fail_fast && !failed_tables.is_empty() {
+                break;
+            }
+            if self.config.tables.settings(table).load != LoadMode::DeleteMissing
+                || failed_tables.contains(table)
+            {
+                continue;
+            }
+            let started = Instant::now();
+            let result = match shared.as_ref() {
+                Some(snapshot) => snapshot
+                    .with(|source_conn| self.delete_missing(source_conn, &mut target_conn, table)),
+                None => self.delete_missing(
+                    &mut get_pooled_connection(&source_pool)?,
+                    &mut target_conn,
                     table,
-                    column_names.join(", "),
-                    values_as_strings.join(", ")
-                );
+                ),
+            };
+            reporter.table_phase(table, Phase::Data, started.elapsed());
+            if let Err(err) = result {
+                self.record_failure(table, &err, &mut failed_tables);
+            }
+        }
 
-                let insert_result = target_conn.exec_drop(insert_query, ());
+        if !failed_tables.is_empty() {
+            return Err(CustomError::TablesFailed {
+                phase: Phase::Data,
+                failed: failed_tables,
+                total: attempted.len(),
+            });
+        }
+        Ok(())
+    }
 
-                match insert_result {
-                    Ok(_) => {}
-                    Err(err) => {
-                        println!("Error: {:?}", err);
-                        return Err(CustomError::QueryExecution);
-                    }
+    fn record_failure(&self, table: &str, err: &CustomError, failed_tables: &mut Vec<String>) {
+        Reporter::new().failure(table, Phase::Data, err);
+        println!("Failed to migrate data for table {}: {}", table, err);
+        failed_tables.push(table.to_string());
+    }
+
+    fn get_load_order(&self, source_conn: &mut PooledConn) -> CustomResult<LoadOrder> {
+        let query = "SELECT DISTINCT TABLE_NAME, REFERENCED_TABLE_NAME \
+                     FROM information_schema.KEY_COLUMN_USAGE \
+                     WHERE TABLE_SCHEMA = DATABASE() \
+                     AND REFERENCED_TABLE_SCHEMA = DATABASE() \
+                     AND REFERENCED_TABLE_NAME IS NOT NULL";
+        let references: Vec<(String, String)> = source_conn
+            .query(query)
+            .map_err(|err| CustomError::query(Phase::Data, query, err))?;
+
+        let order = sort_tables(&self.config.tables.data_source, &references);
+        println!("Table load order: {:?}", order.tables);
+        if !order.cyclic.is_empty() {
+            println!(
+                "Tables in a foreign key cycle, loaded with FOREIGN_KEY_CHECKS = 0: {:?}",
+                order.cyclic
+            );
+        }
+        Ok(order)
+    }
+
+    // One snapshot connection per worker for the whole run, so tables copied minutes
+    // apart still agree on which rows exist.
+    fn open_snapshot(&self, workers: usize) -> CustomResult<Option<SnapshotConnections>> {
+        if !self.config.technology.consistent_snapshot {
+            return Ok(None);
+        }
+
+        let snapshot = SnapshotConnections::open(&self.config.source, workers)?;
+        if snapshot.is_some() {
+            println!("Reading the source from a consistent snapshot");
+        }
+        Ok(snapshot)
+    }
+
+    fn plan_jobs(
+        &self,
+        source_pool: &Pool,
+        table: &str,
+        cyclic: bool,
+        shared: Option<&SnapshotConnections>,
+        snapshots: &mut BTreeMap<String, SnapshotConnections>,
+    ) -> CustomResult<Vec<Job>> {
+        let to_jobs = |chunks: Vec<Chunk>| {
+            chunks
+                .into_iter()
+                .map(|chunk| Job {
+                    table: table.to_string(),
+                    chunk: Some(chunk),
+                })
+                .collect()
+        };
+        // Ranges of an interrupted run are kept, whatever the table holds by now
+        if let Some(chunks) = Checkpoint::new().remaining_chunks(table) {
+            println!("Resuming table {} with {} chunks left", table, chunks.len());
+            return Ok(to_jobs(chunks));
+        }
+
+        let settings = self.config.tables.settings(table);
+        let whole = vec![Job {
+            table: table.to_string(),
+            chunk: None,
+        }];
+        if settings.chunks <= 1 {
+            return Ok(whole);
+        }
+        if settings.upserts() {
+            println!("Table {} is upserted, copying it in one piece", table);
+            return Ok(whole);
+        }
+        if cyclic {
+            // FOREIGN_KEY_CHECKS is per session, so a cyclic table is loaded in one go
+            println!(
+                "Table {} is in a foreign key cycle, copying it in one piece",
+                table
+            );
+            return Ok(whole);
+        }
+
+        let key = self.get_chunk_key(&mut get_pooled_connection(source_pool)?, table)?;
+        let Some(key) = key else {
+            println!(
+                "Table {} has no integer primary key, copying it in one piece",
+                table
+            );
+            return Ok(whole);
+        };
+
+        // One reader per worker that can be copying a chunk of this table at once
+        let snapshot = match shared.is_none() && settings.snapshot {
+            true => {
+                let readers = settings.chunks.min(self.config.workers());
+                SnapshotConnections::open(&self.config.source, readers)?
+            }
+            false => None,
+        };
+
+        let column = format!("`{}`", key);
+        let range = match shared.or(snapshot.as_ref()) {
+            Some(snapshot) => snapshot.with(|conn| self.get_key_range(conn, table, &column))?,
+            None => self.get_key_range(&mut get_pooled_connection(source_pool)?, table, &column)?,
+        };
+
+        let chunks = split(range, settings.chunks, &column, |value| value.to_string());
+        println!("Copying table {} in {} chunks", table, chunks.len());
+        Checkpoint::new().plan_chunks(table, &chunks);
+        if let Some(snapshot) = snapshot {
+            snapshots.insert(table.to_string(), snapshot);
+        }
+
+        Ok(to_jobs(chunks))
+    }
+
+    fn get_chunk_key(
+        &self,
+        source_conn: &mut PooledConn,
+        table: &str,
+    ) -> CustomResult<Option<String>> {
+        let query = "SELECT COLUMN_NAME, DATA_TYPE FROM information_schema.COLUMNS \
+                     WHERE TABLE_SCHEMA = DATABASE() AND TABLE_NAME = ? AND COLUMN_KEY = 'PRI'";
+        let key_columns: Vec<(String, String)> = source_conn
+            .exec(query, (table,))
+            .map_err(|err| CustomError::query(Phase::Data, query, err).with_table(table))?;
+
+        Ok(match key_columns.as_slice() {
+            [(column, data_type)] if INTEGER_TYPES.contains(&data_type.as_str()) => {
+                Some(column.clone())
+            }
+            _ => None,
+        })
+    }
+
+    fn get_key_range(
+        &self,
+        source_conn: &mut impl Queryable,
+        table: &str,
+        column: &str,
+    ) -> CustomResult<Option<(i64, i64)>> {
+        let range_query = format!("SELECT MIN({0}), MAX({0}) FROM {1}", column, table);
+        let range: Option<(Option<i64>, Option<i64>)> = source_conn
+            .query_first(&range_query)
+            .map_err(|err| CustomError::query(Phase::Data, range_query, err).with_table(table))?;
+
+        Ok(range.and_then(|(min, max)| min.zip(max)))
+    }
+
+    fn run_job(
+        &self,
+        source_pool: &Pool,
+        target_pool: &Pool,
+        job: &Job,
+        order: &LoadOrder,
+        snapshot: Option<&SnapshotConnections>,
+    ) -> CustomResult<()> {
+        match &job.chunk {
+            Some(chunk) => self.copy_chunk(source_pool, target_pool, &job.table, chunk, snapshot),
+            None => self.load_table(
+                source_pool,
+                target_pool,
+                &job.table,
+                order.cyclic.contains(&job.table),
+                snapshot,
+            ),
+        }
+    }
+
+    fn copy_chunk(
+        &self,
+        source_pool: &Pool,
+        target_pool: &Pool,
+        table: &str,
+        chunk: &Chunk,
+        snapshot: Option<&SnapshotConnections>,
+    ) -> CustomResult<()> {
+        let started = Instant::now();
+        let result = match snapshot {
+            Some(snapshot) => {
+                snapshot.with(|conn| self.read_chunk(conn, target_pool, table, chunk))
+            }
+            None => self.read_chunk(
+                &mut get_pooled_connection(source_pool)?,
+                target_pool,
+                table,
+                chunk,
+            ),
+        };
+        Reporter::new().table_phase(table, Phase::Data, started.elapsed());
+
+        result
+    }
+
+    fn read_chunk(
+        &self,
+        source_conn: &mut impl Queryable,
+        target_pool: &Pool,
+        table: &str,
+        chunk: &Chunk,
+    ) -> CustomResult<()> {
+        if Plan::new().is_dry_run() {
+            return self.plan_insert(source_conn, table, Some(chunk));
+        }
+
+        let retries = self.config.technology.chunk_retries;
+        let mut attempt = 0;
+        loop {
+            match self.try_copy_chunk(source_conn, target_pool, table, chunk) {
+                Ok(_) => return Ok(()),
+                Err(err) if attempt < retries => {
+                    attempt += 1;
+                    println!(
+                        "Failed to copy {} of table {}, retrying ({}/{}): {}",
+                        chunk, table, attempt, retries, err
+                    );
                 }
+                Err(err) => return Err(err),
             }
-            println!("Migrated data for table: {}", table);
         }
+    }
+
+    fn try_copy_chunk(
+        &self,
+        source_conn: &mut impl Queryable,
+        target_pool: &Pool,
+        table: &str,
+        chunk: &Chunk,
+    ) -> CustomResult<()> {
+        let mut target_conn = get_pooled_connection(target_pool)?;
+        // Nothing of a failed chunk is kept, so it can simply be copied again
+        let mut transaction = target_conn
+            .start_transaction(TxOpts::default())
+            .map_err(|err| CustomError::query(Phase::Data, "START TRANSACTION", err))?;
+
+        let mut progress = Progress::default();
+        self.copy_rows(
+            source_conn,
+            &mut transaction,
+            table,
+            Some(&chunk.condition),
+            false,
+            &mut progress,
+        )?;
+        transaction
+            .commit()
+            .map_err(|err| CustomError::query(Phase::Data, "COMMIT", err).with_table(table))?;
+        Checkpoint::new().chunk_copied(table, chunk);
+
+        Reporter::new().rows(table, progress.rows, progress.rows, progress.bytes);
+        println!(
+            "Copied {} of table {}: {} rows",
+            chunk, table, progress.rows
+        );
         Ok(())
     }
 
-    fn get_columns(&self, connection: &mut PooledConn, table: &str) -> CustomResult<Vec<String>> {
+    // Each table takes its own connections, so workers never share a session.
+    fn load_table(
+        &self,
+        source_pool: &Pool,
+        target_pool: &Pool,
+        table: &str,
+        cyclic: bool,
+        snapshot: Option<&SnapshotConnections>,
+    ) -> CustomResult<()> {
+        let mut target_conn = get_pooled_connection(target_pool)?;
+
+        let started = Instant::now();
+        let result = match snapshot {
+            Some(snapshot) => snapshot
+                .with(|source_conn| self.read_table(source_conn, &mut target_conn, table, cyclic)),
+            None => self.read_table(
+                &mut get_pooled_connection(source_pool)?,
+                &mut target_conn,
+                table,
+                cyclic,
+            ),
+        };
+        Reporter::new().table_phase(table, Phase::Data, started.elapsed());
+
+        result
+    }
+
+    fn read_table(
+        &self,
+        source_conn: &mut impl Queryable,
+        target_conn: &mut PooledConn,
+        table: &str,
+        cyclic: bool,
+    ) -> CustomResult<()> {
+        match cyclic {
+            true => self.migrate_cyclic_table(source_conn, target_conn, table),
+            false => self.migrate_table(source_conn, target_conn, table),
+        }
+    }
+
+    // No order satisfies a reference cycle, so its tables skip the checks while loading.
+    // Loaded rows are not re-checked when they are turned back on.
+    fn migrate_cyclic_table(
+        &self,
+        source_conn: &mut impl Queryable,
+        target_conn: &mut PooledConn,
+        table: &str,
+    ) -> CustomResult<()> {
+        self.set_foreign_key_checks(target_conn, false)?;
+        let result = self.migrate_table(source_conn, target_conn, table);
+        let enabled = self.set_foreign_key_checks(target_conn, true);
+
+        result.and(enabled)
+    }
+
+    fn migrate_table(
+        &self,
+        source_conn: &mut impl Queryable,
+        target_conn: &mut PooledConn,
+        table: &str,
+    ) -> CustomResult<()> {
+        let settings = self.config.tables.settings(table);
+        if let Some(column) = &settings.watermark {
+            return self.migrate_increment(source_conn, target_conn, table, column);
+        }
+        if Plan::new().is_dry_run() {
+            return self.plan_insert(source_conn, table, None);
+        }
+        let upsert = settings.upserts();
+        if upsert {
+            self.check_unique_key(target_conn, table)?;
+        }
+
+        println!("Migrating data for table: {}", table);
+        let mut progress = Progress::default();
+        let result = self.copy_rows(source_conn, target_conn, table, None, upsert, &mut progress);
+        // Batches are committed one by one, so rows written before a failure stay
+        Reporter::new().rows(table, progress.rows, progress.rows, progress.bytes);
+        result?;
+
+        println!("Migrated {} rows for table: {}", progress.rows, table);
+        Ok(())
+    }
+
+    // Rows above the stored watermark are upserted, so rows changed since the last run
+    // are updated in place and a failed run can simply be repeated.
+    fn migrate_increment(
+        &self,
+        source_conn: &mut impl Queryable,
+        target_conn: &mut PooledConn,
+        table: &str,
+        column: &str,
+    ) -> CustomResult<()> {
+        let increment = self.get_increment(source_conn, target_conn, table, column)?;
+        if Plan::new().is_dry_run() {
+            let count_query = format!(
+                "SELECT COUNT(*) FROM {} WHERE {}",
+                table, increment.condition
+            );
+            let count: u64 = source_conn
+                .query_first(&count_query)
+                .map_err(|err| {
+                    CustomError::query(Phase::Data, &count_query, err).with_table(table)
+                })?
+                .unwrap_or(0);
+            Plan::new().note(
+                Phase::Data,
+                format!(
+                    "UPSERT INTO {} WHERE {}: {} rows",
+                    table, increment.condition, count
+                )
+                .as_str(),
+            );
+            return Ok(());
+        }
+        let Some(next) = &increment.next else {
+            println!("No data to migrate for table: {}", table);
+            return Ok(());
+        };
+        self.check_unique_key(target_conn, table)?;
+
+        println!(
+            "Migrating data for table {} from {} {:?} to {}",
+            table, column, increment.last, next
+        );
+        let mut progress = Progress::default();
+        let result = self.copy_rows(
+            source_conn,
+            target_conn,
+            table,
+            Some(&increment.condition),
+            true,
+            &mut progress,
+        );
+        Reporter::new().rows(table, progress.rows, progress.rows, progress.bytes);
+        result?;
+
+        println!("Migrated {} rows for table: {}", progress.rows, table);
+        self.store_watermark(target_conn, table, column, next)
+    }
+
+    fn get_increment(
+        &self,
+        source_conn: &mut impl Queryable,
+        target_conn: &mut PooledConn,
+        table: &str,
+        column: &str,
+    ) -> CustomResult<Increment> {
+        let expression = format!("`{}`", column);
+        let last = self.read_watermark(target_conn, table, column)?;
+        let max_query = format!(
+            "SELECT CAST(MAX({}) AS CHAR) FROM {} WHERE {}",
+            expression,
+            table,
+            above(&expression, last.as_deref(), literal)
+        );
+        let next: Option<Option<String>> = source_conn
+            .query_first(&max_query)
+            .map_err(|err| CustomError::query(Phase::Data, &max_query, err).with_table(table))?;
+
+        Ok(Increment::new(&expression, last, next.flatten(), literal))
+    }
+
+    // ON DUPLICATE KEY UPDATE matches on the unique index a row collides with, so a
+    // configured `key` has to be one of them.
+    fn check_unique_key(&self, target_conn: &mut PooledConn, table: &str) -> CustomResult<()> {
+        let query = "SELECT INDEX_NAME, COLUMN_NAME FROM information_schema.STATISTICS \
+                     WHERE TABLE_SCHEMA = DATABASE() AND TABLE_NAME = ? AND NON_UNIQUE = 0 \
+                     ORDER BY INDEX_NAME, SEQ_IN_INDEX";
+        let columns: Vec<(String, String)> = target_conn
+            .exec(query, (table,))
+            .map_err(|err| CustomError::query(Phase::Data, query, err).with_table(table))?;
+        let mut indexes: BTreeMap<String, Vec<String>> = BTreeMap::new();
+        for (index, column) in columns {
+            indexes.entry(index).or_default().push(column);
+        }
+
+        if indexes.is_empty() {
+            return Err(CustomError::structure(
+                Phase::Data,
+                table,
+                "no primary or unique key to match upserted rows on",
+            ));
+        }
+        let mut key = self.config.tables.settings(table).key;
+        key.sort();
+        let matches = indexes.into_values().any(|mut columns| {
+            columns.sort();
+            columns == key
+        });
+        if !key.is_empty() && !matches {
+            return Err(CustomError::structure(
+                Phase::Data,
+                table,
+                "`key` is not supported for MySQL upserts unless it is exactly the columns \
+                 of a primary or unique key",
+            ));
+        }
+
+        Ok(())
+    }
+
+    // The configured `key`, or the primary key of the target table.
+    fn get_key(&self, target_conn: &mut PooledConn, table: &str) -> CustomResult<Vec<String>> {
+        let settings = self.config.tables.settings(table);
+        if !settings.key.is_empty() {
+            return Ok(settings.key);
+        }
+
+        let query = "SELECT COLUMN_NAME FROM information_schema.KEY_COLUMN_USAGE \
+                     WHERE TABLE_SCHEMA = DATABASE() AND TABLE_NAME = ? \
+                     AND CONSTRAINT_NAME = 'PRIMARY' ORDER BY ORDINAL_POSITION";
+        let key: Vec<String> = target_conn
+            .exec(query, (table,))
+            .map_err(|err| CustomError::query(Phase::Data, query, err).with_table(table))?;
+        if key.is_empty() {
+            return Err(CustomError::structure(
+                Phase::Data,
+                table,
+                "no primary key to match rows on, set `key` in the table settings",
+            ));
+        }
+
+        Ok(key)
+    }
+
+    // Source keys are staged in a temporary table of the target session, so neither side
+    // is held in memory. A key with a NULL part can't be matched, so its row is kept.
+    fn delete_missing(
+        &self,
+        source_conn: &mut impl Queryable,
+        target_conn: &mut PooledConn,
+        table: &str,
+    ) -> CustomResult<()> {
+        let key: Vec<String> = self
+            .get_key(target_conn, table)?
+            .iter()
+            .map(|column| format!("`{}`", column))
+            .collect();
+        if Plan::new().is_dry_run() {
+            Plan::new().note(
+                Phase::Data,
+                format!(
+                    "DELETE FROM {} rows whose ({}) is not in the source",
+                    table,
+                    key.join(", ")
+                )
+                .as_str(),
+            );
+            return Ok(());
+        }
+
+        let staged: Vec<String> = (0..key.len())
+            .map(|index| format!("key_{}", index))
+            .collect();
+        let not_null: Vec<String> = key
+            .iter()
+            .map(|column| format!("{} IS NOT NULL", column))
+            .collect();
+        // Staged columns take the types of the target columns
+        let columns: Vec<String> = key
+            .iter()
+            .zip(&staged)
+            .map(|(column, staged)| format!("{} AS {}", column, staged))
+            .collect();
+        let create_queries = [
+            format!("DROP TEMPORARY TABLE IF EXISTS {}", KEYS_TABLE),
+            format!(
+                "CREATE TEMPORARY TABLE {} AS SELECT {} FROM {} LIMIT 0",
+                KEYS_TABLE,
+                columns.join(", "),
+                table
+            ),
+        ];
+        for query in create_queries {
+            exec_drop(target_conn, Phase::Data, &query)
+                .map_err(|err| CustomError::query(Phase::Data, query, err).with_table(table))?;
+        }
+
+        let select_query = format!(
+            "SELECT {} FROM {} WHERE {}",
+            key.join(", "),
+            table,
+            not_null.join(" AND ")
+        );
+        let batch_size = self.config.technology.batch_size.max(1);
+        let rows = source_conn
+            .query_iter(&select_query)
+            .map_err(|err| CustomError::query(Phase::Data, &select_query, err).with_table(table))?;
+        let mut batch: Vec<String> = Vec::with_capacity(batch_size);
+        for row in rows {
+            let row = row.map_err(|err| {
+                CustomError::query(Phase::Data, &select_query, err).with_table(table)
+            })?;
+            let values: Vec<String> = row
+                .unwrap()
+                .into_iter()
+                .map(|value| value.as_sql(false))
+                .collect();
+            batch.push(format!("({})", values.join(", ")));
+            if batch.len() >= batch_size {
+                self.stage_keys(target_conn, table, &batch)?;
+                batch.clear();
+            }
+        }
+        if !batch.is_empty() {
+            self.stage_keys(target_conn, table, &batch)?;
+        }
+
+        let matches: Vec<String> = key
+            .iter()
+            .zip(&staged)
+            .map(|(column, staged)| format!("k.{} = t.{}", staged, column))
+            .collect();
+        let delete_query = format!(
+            "DELETE t FROM {} t WHERE {} AND NOT EXISTS (SELECT 1 FROM {} k WHERE {})",
+            table,
+            not_null.join(" AND "),
+            KEYS_TABLE,
+            matches.join(" AND ")
+        );
+        exec_drop(target_conn, Phase::Data, &delete_query)
+            .map_err(|err| CustomError::query(Phase::Data, &delete_query, err).with_table(table))?;
+        let deleted = target_conn.affected_rows();
+        let drop_query = format!("DROP TEMPORARY TABLE {}", KEYS_TABLE);
+        exec_drop(target_conn, Phase::Data, &drop_query)
+            .map_err(|err| CustomError::query(Phase::Data, drop_query, err).with_table(table))?;
+
+        println!(
+            "Deleted {} rows of table {} missing in the source",
+            deleted, table
+        );
+        Ok(())
+    }
+
+    fn stage_keys(
+        &self,
+        target_conn: &mut PooledConn,
+        table: &str,
+        batch: &[String],
+    ) -> CustomResult<()> {
+        let query = format!("INSERT INTO {} VALUES {}", KEYS_TABLE, batch.join(", "));
+        exec_drop(target_conn, Phase::Data, &query)
+            .map_err(|err| CustomError::query(Phase::Data, query, err).with_table(table))
+    }
+
+    fn create_state_table(&self, target_conn: &mut PooledConn) -> CustomResult<()> {
+        let query = format!(
+            "CREATE TABLE IF NOT EXISTS {} (\
+             table_name VARCHAR(255) PRIMARY KEY, \
+             watermark_column VARCHAR(255) NOT NULL, \
+             watermark TEXT NOT NULL, \
+             synced_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP)",
+            STATE_TABLE
+        );
+        exec_drop(target_conn, Phase::Data, &query)
+            .map_err(|err| CustomError::query(Phase::Data, query, err))
+    }
+
+    // A watermark stored for another column starts the table over.
+    fn read_watermark(
+        &self,
+        target_conn: &mut PooledConn,
+        table: &str,
+        column: &str,
+    ) -> CustomResult<Option<String>> {
+        // Only recorded in the plan on --dry-run, so the table may not exist yet
+        let exists_query = "SELECT COUNT(*) FROM information_schema.TABLES \
+                            WHERE TABLE_SCHEMA = DATABASE() AND TABLE_NAME = ?";
+        let exists: Option<u64> = target_conn
+            .exec_first(exists_query, (STATE_TABLE,))
+            .map_err(|err| CustomError::query(Phase::Data, exists_query, err).with_table(table))?;
+        if exists.unwrap_or(0) == 0 {
+            return Ok(None);
+        }
+
+        let query = format!(
+            "SELECT watermark FROM {} WHERE table_name = ? AND watermark_column = ?",
+            STATE_TABLE
+        );
+        target_conn
+            .exec_first(&query, (table, column))
+            .map_err(|err| CustomError::query(Phase::Data, query, err).with_table(table))
+    }
+
+    fn store_watermark(
+        &self,
+        target_conn: &mut PooledConn,
+        table: &str,
+        column: &str,
+        watermark: &str,
+    ) -> CustomResult<()> {
+        let query = format!(
+            "INSERT INTO {} (table_name, watermark_column, watermark) VALUES ({}, {}, {}) \
+             ON DUPLICATE KEY UPDATE watermark_column = VALUES(watermark_column), \
+             watermark = VALUES(watermark), synced_at = CURRENT_TIMESTAMP",
+            STATE_TABLE,
+            literal(table),
+            literal(column),
+            literal(watermark)
+        );
+        exec_drop(target_conn, Phase::Data, &query)
+            .map_err(|err| CustomError::query(Phase::Data, query, err).with_table(table))
+    }
+
+    fn copy_rows(
+        &self,
+        source_conn: &mut impl Queryable,
+        target_conn: &mut impl Queryable,
+        table: &str,
+        condition: Option<&str>,
+        upsert: bool,
+        progress: &mut Progress,
+    ) -> CustomResult<()> {
+        let column_names: Vec<String> = self
+            .get_columns(source_conn, table)?
+            .iter()
+            .map(|column| format!("`{}`", column))
+            .collect();
+        let mut select_query = format!("SELECT {} FROM {}", column_names.join(", "), table);
+        if let Some(condition) = condition {
+            select_query = format!("{} WHERE {}", select_query, condition);
+        }
+
+        // One placeholder per value, and a statement can't carry more than 65535 of them.
+        let batch_size = self
+            .config
+            .technology
+            .batch_size
+            .min(MAX_PLACEHOLDERS / column_names.len().max(1))
+            .max(1);
+
+        // exec_iter uses the binary protocol, so values arrive typed (ints, dates,
+        // decimals, blobs) and are bound back unchanged. Rows are read off the wire
+        // as they are consumed, so at most one batch is held in memory.
+        let rows = source_conn.exec_iter(&select_query, ()).map_err(|err| {
+            CustomError::query(Phase::Data, select_query.as_str(), err).with_table(table)
+        })?;
+
+        let mut batch: Vec<mysql::Value> = Vec::with_capacity(batch_size * column_names.len());
+        let mut batch_rows = 0;
+        for row in rows {
+            let row: Row = row.map_err(|err| {
+                CustomError::query(Phase::Data, select_query.as_str(), err).with_table(table)
+            })?;
+            batch.extend(row.unwrap());
+            batch_rows += 1;
+
+            if batch_rows >= batch_size {
+                self.write_batch(
+                    target_conn,
+                    table,
+                    &column_names,
+                    std::mem::take(&mut batch),
+                    upsert,
+                    progress,
+                )?;
+                batch_rows = 0;
+            }
+        }
+        if batch_rows > 0 {
+            self.write_batch(target_conn, table, &column_names, batch, upsert, progress)?;
+        }
+
+        Ok(())
+    }
+
+    fn write_batch(
+        &self,
+        target_conn: &mut impl Queryable,
+        table: &str,
+        column_names: &[String],
+        values: Vec<mysql::Value>,
+        upsert: bool,
+        progress: &mut Progress,
+    ) -> CustomResult<()> {
+        let rows = values.len() / column_names.len().max(1);
+        let placeholders = format!("({})", vec!["?"; column_names.len()].join(", "));
+        let mut insert_query = format!(
+            "INSERT INTO {} ({}) VALUES {}",
+            table,
+            column_names.join(", "),
+            vec![placeholders; rows].join(", ")
+        );
+        if upsert {
+            let updates: Vec<String> = column_names
+                .iter()
+                .map(|column| format!("{0} = VALUES({0})", column))
+                .collect();
+            insert_query = format!(
+                "{} ON DUPLICATE KEY UPDATE {}",
+                insert_query,
+                updates.join(", ")
+            );
+        }
+        let bytes: u64 = values.iter().map(|value| value.bin_len()).sum();
+
+        // Same-sized batches share the text, so the driver's statement cache
+        // prepares each shape only once.
+        target_conn
+            .exec_drop(&insert_query, Params::Positional(values))
+            .map_err(|err| CustomError::query(Phase::Data, insert_query, err).with_table(table))?;
+
+        progress.rows += rows as u64;
+        progress.bytes += bytes;
+        Ok(())
+    }
+
+    fn plan_insert(
+        &self,
+        source_conn: &mut impl Queryable,
+        table: &str,
+        chunk: Option<&Chunk>,
+    ) -> CustomResult<()> {
+        let columns = self.get_columns(source_conn, table)?;
+        let mut count_query = format!("SELECT COUNT(*) FROM {}", table);
+        if let Some(chunk) = chunk {
+            count_query = format!("{} WHERE {}", count_query, chunk.condition);
+        }
+        let count: u64 = source_conn
+            .query_first(&count_query)
+            .map_err(|err| CustomError::query(Phase::Data, &count_query, err).with_table(table))?
+            .unwrap_or(0);
+
+        let command = match self.config.tables.settings(table).upserts() {
+            true => "UPSERT INTO",
+            false => "INSERT INTO",
+        };
+        Plan::new().note(
+            Phase::Data,
+            format!(
+                "{} {} ({}){}: {} rows",
+                command,
+                table,
+                columns
+                    .iter()
+                    .map(|column| format!("`{}`", column))
+                    .collect::<Vec<String>>()
+                    .join(", "),
+                chunk
+                    .map(|chunk| format!(" {} WHERE {}", chunk, chunk.condition))
+                    .unwrap_or_default(),
+                count
+            )
+            .as_str(),
+        );
+        Ok(())
+    }
+
+    fn get_columns(
+        &self,
+        connection: &mut impl Queryable,
+        table: &str,
+    ) -> CustomResult<Vec<String>> {
         let column_query = format!("SHOW COLUMNS FROM {};", table);
         let rows: Vec<String> = connection
-            .query_map(column_query, |row: Row| -> CustomResult<String> {
+            .query_map(&column_query, |row: Row| -> CustomResult<String> {
                 let columns = row.columns_ref();
 
                 let mut index: Option<usize> = None;
@@ -88,7 +1046,11 @@ impl DataMigrator {
                 }
 
                 let value = (match index {
-                    None => Err(CustomError::DbTableStructure),
+                    None => Err(CustomError::structure(
+                        Phase::Data,
+                        table,
+                        "`Field` column missing from SHOW COLUMNS output",
+                    )),
                     Some(value) => {
                         let query: String =
                             row.get(value).expect("Value should be present in the Roo");
@@ -99,57 +1061,36 @@ impl DataMigrator {
 
                 Ok(value)
             })
-            .map_err(|err| {
-                println!("Error: {:?}", err);
-                CustomError::QueryExecution
-            })?
+            .map_err(|err| CustomError::query(Phase::Data, column_query, err).with_table(table))?
             .into_iter()
-            .filter_map(|el| el.map_err(|err| err).ok())
+            .filter_map(|el| el.ok())
             .collect();
 
         Ok(rows)
     }
 
-    fn get_data(
-        &self,
-        connection: &mut PooledConn,
-        table: &str,
-    ) -> CustomResult<Vec<HashMap<String, mysql::Value>>> {
-        let columns = self.get_columns(connection, table)?;
-        let data: Vec<HashMap<String, mysql::Value>> = connection
-            .query_map(format!("SELECT * FROM {}", table), |row: Row| {
-                let mut map: HashMap<String, mysql::Value> = HashMap::new();
-                for (index, column_name) in columns.iter().enumerate() {
-                    map.insert(column_name.clone(), row.get(index).unwrap());
-                }
-                map
-            })
-            .unwrap();
-
-        Ok(data)
-    }
-
     fn truncate_table(&self, connection: &mut PooledConn, table: &str) -> CustomResult<()> {
-        connection
-            .query_drop("SET FOREIGN_KEY_CHECKS = 0;")
-            .map_err(|err| {
-                println!("Error disabling foreign key checks: {:?}", err);
-                CustomError::QueryExecution
-            })?;
+        self.set_foreign_key_checks(connection, false)?;
 
         let truncate_query = format!("TRUNCATE TABLE {};", table);
-        connection.exec_drop(truncate_query, ()).map_err(|err| {
-            println!("Error truncating table: {:?}", err);
-            CustomError::QueryExecution
-        })?;
+        let result = exec_drop(connection, Phase::Data, &truncate_query)
+            .map_err(|err| CustomError::query(Phase::Data, truncate_query, err).with_table(table));
+        let enabled = self.set_foreign_key_checks(connection, true);
 
-        connection
-            .query_drop("SET FOREIGN_KEY_CHECKS = 1;")
-            .map_err(|err| {
-                println!("Error enabling foreign key checks: {:?}", err);
-                CustomError::QueryExecution
-            })?;
+        result.and(enabled)
+    }
 
-        Ok(())
+    fn set_foreign_key_checks(
+        &self,
+        connection: &mut PooledConn,
+        enabled: bool,
+    ) -> CustomResult<()> {
+        let query = format!("SET FOREIGN_KEY_CHECKS = {};", u8::from(enabled));
+        exec_drop(connection, Phase::Data, &query)
+            .map_err(|err| CustomError::query(Phase::Data, query, err))
     }
 }
+
+fn literal(value: &str) -> String {
+    mysql::Value::from(value).as_sql(false)
+}
@@ -1,11 +1,33 @@
+use std::collections::HashMap;
+use std::path::PathBuf;
+
+use mysql::prelude::Queryable;
 use mysql::*;
+use percent_encoding::{utf8_percent_encode, AsciiSet, NON_ALPHANUMERIC};
+use url::form_urlencoded;
 
 use crate::config::DbConfig;
-use crate::error::{ CustomResult, CustomError };
+use crate::error::{CustomError, CustomResult, Phase};
+use crate::plan::Plan;
+
+// Everything except unreserved characters (RFC 3986) is escaped in userinfo and path.
+const URL_COMPONENT: &AsciiSet = &NON_ALPHANUMERIC
+    .remove(b'-')
+    .remove(b'_')
+    .remove(b'.')
+    .remove(b'~');
 
 pub fn get_connection(db_config: &DbConfig) -> CustomResult<PooledConn> {
     let pool = get_connections_pool(db_config)?;
+    get_pooled_connection(&pool)
+}
+
+/// Single connection outside any pool, for sessions that must not take a worker's slot.
+pub fn get_standalone_connection(db_config: &DbConfig) -> CustomResult<Conn> {
+    Conn::new(get_opts(db_config)?).map_err(CustomError::connection)
+}
 
+pub fn get_pooled_connection(pool: &Pool) -> CustomResult<PooledConn> {
     let connection = match pool.get_conn() {
         Ok(conn) => {
             println!("Got connection from Pool");
@@ -13,7 +35,7 @@ pub fn get_connection(db_config: &DbConfig) -> CustomResult<PooledConn> {
         }
         Err(err) => {
             println!("Can't get connection from Pool: {:#?}", err);
-            return Err(CustomError::DbConnection);
+            return Err(CustomError::connection(err));
         }
     };
 
@@ -21,8 +43,8 @@ pub fn get_connection(db_config: &DbConfig) -> CustomResult<PooledConn> {
 }
 
 pub fn get_connections_pool(db_config: &DbConfig) -> CustomResult<Pool> {
-    let url = get_url(db_config);
-    let pool = Pool::new(url.as_str());
+    let opts = get_opts(db_config)?;
+    let pool = Pool::new(opts);
 
     match pool {
         Ok(pool) => {
@@ -31,20 +53,122 @@ pub fn get_connections_pool(db_config: &DbConfig) -> CustomResult<Pool> {
         }
         Err(err) => {
             println!("Can't create connection Pool: {:#?}", err);
-            Err(CustomError::DbConnection)
+            Err(CustomError::connection(err))
         }
     }
 }
 
+/// Runs a statement against the target, or only records it in the plan on `--dry-run`.
+pub fn exec_drop(connection: &mut PooledConn, phase: Phase, statement: &str) -> mysql::Result<()> {
+    let plan = Plan::new();
+    if plan.is_dry_run() {
+        plan.statement(phase, statement);
+        return Ok(());
+    }
+
+    connection.query_drop(statement)
+}
+
+fn get_opts(db_config: &DbConfig) -> CustomResult<OptsBuilder> {
+    let url = get_url(db_config);
+    let opts = Opts::from_url(url.as_str())
+        .map_err(|err| CustomError::Config(format!("Invalid mysql connection options: {}", err)))?;
+
+    let constraints = PoolConstraints::new(1, db_config.max_connections() as usize);
+    let mut builder = OptsBuilder::from_opts(opts)
+        .ssl_opts(get_ssl_opts(db_config)?)
+        .pool_opts(PoolOpts::default().with_constraints(constraints.unwrap_or_default()));
+
+    if let Some(application_name) = &db_config.application_name {
+        builder = builder.connect_attrs(Some(HashMap::from([(
+            "program_name",
+            application_name.as_str(),
+        )])));
+    }
+
+    if db_config.read_only {
+        builder = builder.init(vec!["SET SESSION TRANSACTION READ ONLY"]);
+    }
+
+    Ok(builder)
+}
+
+fn get_ssl_opts(db_config: &DbConfig) -> CustomResult<Option<SslOpts>> {
+    // native-tls takes the client certificate and key as one PKCS#12 bundle, so there
+    // is no separate key file to load.
+    if db_config.ssl_key.is_some() {
+        return Err(CustomError::Config(
+            "ssl_key is not supported for mysql: put the client certificate and key in a \
+             PKCS#12 bundle in ssl_cert and its password in ssl_pkcs12_password"
+                .to_string(),
+        ));
+    }
+
+    let sslmode = db_config.sslmode.as_deref().unwrap_or(
+        if db_config.ssl_root_cert.is_some() || db_config.ssl_cert.is_some() {
+            "verify-full"
+        } else {
+            "disable"
+        },
+    );
+
+    let ssl_opts = SslOpts::default()
+        .with_root_cert_path(db_config.ssl_root_cert.clone().map(PathBuf::from))
+        .with_client_identity(db_config.ssl_cert.clone().map(|path| {
+            let identity = ClientIdentity::new(PathBuf::from(path));
+            match &db_config.ssl_pkcs12_password {
+                Some(password) => identity.with_password(password.clone()),
+                None => identity,
+            }
+        }));
+
+    Ok(match sslmode {
+        "require" => Some(
+            ssl_opts
+                .with_danger_accept_invalid_certs(true)
+                .with_danger_skip_domain_validation(true),
+        ),
+        "verify-ca" => Some(ssl_opts.with_danger_skip_domain_validation(true)),
+        "verify-full" => Some(ssl_opts),
+        // The mysql driver has no opportunistic TLS, so allow/prefer connect in plain text.
+        _ => None,
+    })
+}
+
 fn get_url(db_config: &DbConfig) -> String {
-    let url = format!(
-        "mysql://{}:{}@{}:{}/{}",
-        db_config.username,
-        db_config.password,
-        db_config.host,
-        db_config.port,
-        db_config.database
+    let host = if db_config.host.is_empty() {
+        "localhost"
+    } else {
+        db_config.host.as_str()
+    };
+    let port = if db_config.port.is_empty() {
+        String::new()
+    } else {
+        format!(":{}", db_config.port)
+    };
+
+    let mut url = format!(
+        "mysql://{}:{}@{}{}/{}",
+        utf8_percent_encode(&db_config.username, URL_COMPONENT),
+        utf8_percent_encode(&db_config.password, URL_COMPONENT),
+        host,
+        port,
+        utf8_percent_encode(&db_config.database, URL_COMPONENT)
     );
 
+    let mut query = form_urlencoded::Serializer::new(String::new());
+    if let Some(socket) = &db_config.socket {
+        query.append_pair("socket", socket);
+    }
+    for (key, value) in &db_config.params {
+        query.append_pair(key, value);
+    }
+
+    let query = query.finish();
+    if !query.is_empty() {
+        url.push('?');
+        url.push_str(&query);
+    }
+
     url
 }
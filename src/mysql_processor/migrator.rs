@@ -1,20 +1,31 @@
 use crate::{
     config::Config,
     logger::Logger,
-    mysql_processor::{data_migrator::DataMigrator, structure_migrator::StructureMigrator},
-    traits::StructureMigratorTrait,
+    mysql_processor::{
+        data_migrator::DataMigrator, schema_diff::SchemaDiffer, sequence_sync::SequenceSync,
+        structure_migrator::StructureMigrator, structure_sync::StructureSync, verifier::Verifier,
+    },
+    traits::{MigratorTrait, StructureMigratorTrait},
 };
 use std::time::Instant;
 
-use crate::error::CustomResult;
+use crate::config::StructureMode;
+use crate::diff::SchemaDiff;
+use crate::error::{CustomError, CustomResult, Phase};
+use crate::report::Reporter;
 
 pub struct Migrator {
     pub config: Config,
 }
 
-impl Migrator {
-    pub async fn migrate(&self) -> CustomResult<()> {
+impl MigratorTrait for Migrator {
+    fn new(config: Config) -> Self {
+        Self { config }
+    }
+
+    async fn migrate(&self) -> CustomResult<()> {
         let logger = Logger::new();
+        let reporter = Reporter::new();
         let struct_migrator = StructureMigrator {
             config: self.config.clone(),
         };
@@ -22,10 +33,17 @@ impl Migrator {
             config: self.config.clone(),
         };
 
+        let mut structure_failure = None;
         if self.config.technology.copy_structure {
             logger.info("Migrating structure. start");
             let structure_migration_start_time = Instant::now();
-            struct_migrator.migrate().await?;
+            let structure_result = match self.config.technology.structure_mode {
+                StructureMode::Recreate => struct_migrator.migrate().await,
+                StructureMode::Sync => StructureSync {
+                    config: self.config.clone(),
+                }
+                .migrate(),
+            };
             let structure_migration_end_time = Instant::now();
             let structure_migration_elapsed_time =
                 structure_migration_end_time - structure_migration_start_time;
@@ -36,17 +54,81 @@ impl Migrator {
                 )
                 .as_str(),
             );
+            reporter.run_phase(Phase::Structure, structure_migration_elapsed_time);
+
+            if let Err(err) = structure_result {
+                if self.config.run.fail_fast || !matches!(err, CustomError::TablesFailed { .. }) {
+                    return Err(err);
+                }
+                logger.warn("Continuing with data migration after structure failures");
+                structure_failure = Some(err);
+            }
         }
 
         if self.config.technology.copy_data {
             logger.info("Migrating data");
             let data_migration_start_time = Instant::now();
-            data_migrator.migrate()?;
+            let data_result = data_migrator.migrate();
             let data_migration_end_time = Instant::now();
             let data_migration_elapsed_time = data_migration_end_time - data_migration_start_time;
             logger.info(format!("Migrated data in {:?}", data_migration_elapsed_time).as_str());
+            reporter.run_phase(Phase::Data, data_migration_elapsed_time);
+
+            let data_failure = match data_result {
+                Ok(()) => None,
+                Err(err @ CustomError::TablesFailed { .. }) if !self.config.run.fail_fast => {
+                    Some(err)
+                }
+                Err(err) => return Err(err),
+            };
+
+            // Rows are copied with their ids, so sequences have to be moved past them
+            logger.info("Synchronizing sequences");
+            let sequence_sync_start_time = Instant::now();
+            let sequence_sync = SequenceSync {
+                config: self.config.clone(),
+            };
+            let sequence_result = sequence_sync.migrate();
+            let sequence_sync_elapsed_time = sequence_sync_start_time.elapsed();
+            logger.info(
+                format!("Synchronized sequences in {:?}", sequence_sync_elapsed_time).as_str(),
+            );
+            reporter.run_phase(Phase::Sequence, sequence_sync_elapsed_time);
+
+            let verify_result = match self.config.run.verify {
+                true => self.verify().await,
+                false => Ok(()),
+            };
+
+            if let Some(err) = data_failure {
+                return Err(err);
+            }
+            sequence_result?;
+            verify_result?;
         }
 
-        Ok(())
+        structure_failure.map_or(Ok(()), Err)
+    }
+
+    async fn verify(&self) -> CustomResult<()> {
+        let logger = Logger::new();
+        logger.info("Verifying tables");
+        let verify_start_time = Instant::now();
+        let verifier = Verifier {
+            config: self.config.clone(),
+        };
+        let verify_result = verifier.verify();
+        let verify_elapsed_time = verify_start_time.elapsed();
+        logger.info(format!("Verified tables in {:?}", verify_elapsed_time).as_str());
+        Reporter::new().run_phase(Phase::Verify, verify_elapsed_time);
+
+        verify_result
+    }
+
+    async fn diff(&self) -> CustomResult<SchemaDiff> {
+        let differ = SchemaDiffer {
+            config: self.config.clone(),
+        };
+        differ.diff()
     }
 }
@@ -1,4 +1,9 @@
 pub mod migrator;
 pub mod db;
 pub mod data_migrator;
+pub mod schema_diff;
+pub mod sequence_sync;
 pub mod structure_migrator;
+pub mod structure_sync;
+pub mod snapshot;
+pub mod verifier;
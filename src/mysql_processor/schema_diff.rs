@@ -0,0 +1,111 @@
+use std::collections::BTreeMap;
+
+use crate::config::Config;
+use crate::diff::{compare, Schema, SchemaDiff, TableSchema};
+use crate::error::{CustomError, CustomResult, Phase};
+use crate::mysql_processor::db::get_connection;
+use crate::mysql_processor::structure_migrator::StructureMigrator;
+use crate::traits::StructureMigratorTrait;
+use mysql::prelude::Queryable;
+use mysql::PooledConn;
+
+/// Reads the catalog of source and target from `information_schema` and compares
+/// them. Enums are part of the column types and AUTO_INCREMENT counters are data,
+/// so only tables are compared.
+pub struct SchemaDiffer {
+    pub config: Config,
+}
+
+impl SchemaDiffer {
+    pub fn diff(&self) -> CustomResult<SchemaDiff> {
+        let mut source_conn = get_connection(&self.config.source)?;
+        let mut target_conn = get_connection(&self.config.target)?;
+
+        let source = self.read_schema(&mut source_conn)?;
+        let target = self.read_schema(&mut target_conn)?;
+
+        Ok(compare(&source, &target))
+    }
+
+    fn read_schema(&self, connection: &mut PooledConn) -> CustomResult<Schema> {
+        let struct_migrator = StructureMigrator {
+            config: self.config.clone(),
+        };
+        let mut schema = Schema::default();
+
+        for table in struct_migrator.get_tables(connection)? {
+            if struct_migrator.skip_reason(&table).is_some() {
+                continue;
+            }
+
+            let table_schema = TableSchema {
+                columns: self.get_columns(connection, &table)?,
+                indexes: self.get_indexes(connection, &table)?,
+                constraints: self.get_constraints(connection, &table)?,
+            };
+            schema.tables.insert(table, table_schema);
+        }
+
+        Ok(schema)
+    }
+
+    fn query_definitions(
+        &self,
+        connection: &mut PooledConn,
+        phase: Phase,
+        query: &str,
+        table: &str,
+    ) -> CustomResult<BTreeMap<String, String>> {
+        let rows: Vec<(String, String)> = connection
+            .exec(query, (table,))
+            .map_err(|err| CustomError::query(phase, query, err).with_table(table))?;
+
+        Ok(rows.into_iter().collect())
+    }
+
+    fn get_columns(
+        &self,
+        connection: &mut PooledConn,
+        table: &str,
+    ) -> CustomResult<BTreeMap<String, String>> {
+        let query = "SELECT COLUMN_NAME, CONCAT(COLUMN_TYPE, \
+                     IF(IS_NULLABLE = 'NO', ' NOT NULL', ''), \
+                     IFNULL(CONCAT(' DEFAULT ', COLUMN_DEFAULT), ''), \
+                     IF(EXTRA = '', '', CONCAT(' ', EXTRA))) \
+                     FROM information_schema.COLUMNS \
+                     WHERE TABLE_SCHEMA = DATABASE() AND TABLE_NAME = ?";
+        self.query_definitions(connection, Phase::Structure, query, table)
+    }
+
+    fn get_indexes(
+        &self,
+        connection: &mut PooledConn,
+        table: &str,
+    ) -> CustomResult<BTreeMap<String, String>> {
+        let query = "SELECT INDEX_NAME, CONCAT(IF(NON_UNIQUE = 0, 'UNIQUE ', ''), INDEX_TYPE, \
+                     ' (', GROUP_CONCAT(COLUMN_NAME ORDER BY SEQ_IN_INDEX SEPARATOR ', '), ')') \
+                     FROM information_schema.STATISTICS \
+                     WHERE TABLE_SCHEMA = DATABASE() AND TABLE_NAME = ? \
+                     GROUP BY INDEX_NAME, NON_UNIQUE, INDEX_TYPE";
+        self.query_definitions(connection, Phase::Index, query, table)
+    }
+
+    fn get_constraints(
+        &self,
+        connection: &mut PooledConn,
+        table: &str,
+    ) -> CustomResult<BTreeMap<String, String>> {
+        let query = "SELECT tc.CONSTRAINT_NAME, CONCAT(tc.CONSTRAINT_TYPE, \
+                     ' (', GROUP_CONCAT(k.COLUMN_NAME ORDER BY k.ORDINAL_POSITION SEPARATOR ', '), ')', \
+                     IFNULL(CONCAT(' REFERENCES ', MAX(k.REFERENCED_TABLE_NAME), ' (', \
+                     GROUP_CONCAT(k.REFERENCED_COLUMN_NAME ORDER BY k.ORDINAL_POSITION SEPARATOR ', '), \
+                     ')'), '')) \
+                     FROM information_schema.TABLE_CONSTRAINTS tc \
+                     JOIN information_schema.KEY_COLUMN_USAGE k \
+                     ON k.CONSTRAINT_SCHEMA = tc.CONSTRAINT_SCHEMA \
+                     AND k.CONSTRAINT_NAME = tc.CONSTRAINT_NAME AND k.TABLE_NAME = tc.TABLE_NAME \
+                     WHERE tc.TABLE_SCHEMA = DATABASE() AND tc.TABLE_NAME = ? \
+                     GROUP BY tc.CONSTRAINT_NAME, tc.CONSTRAINT_TYPE";
+        self.query_definitions(connection, Phase::Constraint, query, table)
+    }
+}
@@ -0,0 +1,81 @@
+use crate::checkpoint::Checkpoint;
+use crate::config::Config;
+use crate::error::{CustomError, CustomResult, Phase};
+use crate::mysql_processor::db::{exec_drop, get_connection};
+use crate::report::Reporter;
+use mysql::prelude::Queryable;
+use mysql::PooledConn;
+
+/// Post-data phase: aligns each copied table's AUTO_INCREMENT with the source.
+pub struct SequenceSync {
+    pub config: Config,
+}
+
+impl SequenceSync {
+    pub fn migrate(&self) -> CustomResult<()> {
+        let mut source_conn = get_connection(&self.config.source)?;
+        let mut target_conn = get_connection(&self.config.target)?;
+
+        // MySQL 8 caches AUTO_INCREMENT in information_schema for a day by default;
+        // older servers don't know the variable and don't cache, so errors are ignored.
+        let _ = source_conn.query_drop("SET SESSION information_schema_stats_expiry = 0");
+
+        let reporter = Reporter::new();
+        let checkpoint = Checkpoint::new();
+        let mut failed_tables = Vec::new();
+        let mut attempted = 0;
+        for table in &self.config.tables.data_source {
+            if checkpoint.is_done(table, Phase::Sequence) {
+                continue;
+            }
+            attempted += 1;
+            if let Err(err) = self.sync_table(&mut source_conn, &mut target_conn, table) {
+                reporter.failure(table, Phase::Sequence, &err);
+                println!("Failed to sync AUTO_INCREMENT for table {}: {}", table, err);
+                failed_tables.push(table.to_string());
+                if self.config.run.fail_fast {
+                    println!("Stopping after first failure (--fail-fast)");
+                    break;
+                }
+            } else {
+                checkpoint.complete(table, Phase::Sequence);
+            }
+        }
+
+        if !failed_tables.is_empty() {
+            return Err(CustomError::TablesFailed {
+                phase: Phase::Sequence,
+                failed: failed_tables,
+                total: attempted,
+            });
+        }
+        Ok(())
+    }
+
+    fn sync_table(
+        &self,
+        source_conn: &mut PooledConn,
+        target_conn: &mut PooledConn,
+        table: &str,
+    ) -> CustomResult<()> {
+        let query = "SELECT AUTO_INCREMENT FROM information_schema.TABLES \
+                     WHERE TABLE_SCHEMA = DATABASE() AND TABLE_NAME = ?";
+        let auto_increment: Option<Option<u64>> = source_conn
+            .exec_first(query, (table,))
+            .map_err(|err| CustomError::query(Phase::Sequence, query, err).with_table(table))?;
+
+        // NULL for tables without an AUTO_INCREMENT column
+        let Some(value) = auto_increment.flatten() else {
+            return Ok(());
+        };
+
+        // MySQL raises the value to MAX(column) + 1 by itself if the target holds larger ids.
+        let alter_query = format!("ALTER TABLE `{}` AUTO_INCREMENT = {}", table, value);
+        exec_drop(target_conn, Phase::Sequence, &alter_query).map_err(|err| {
+            CustomError::query(Phase::Sequence, alter_query.as_str(), err).with_table(table)
+        })?;
+        println!("Set AUTO_INCREMENT of table {} to {}", table, value);
+
+        Ok(())
+    }
+}
@@ -0,0 +1,100 @@
+use std::sync::Mutex;
+
+use mysql::prelude::Queryable;
+use mysql::Conn;
+
+use crate::config::DbConfig;
+use crate::error::{CustomError, CustomResult, Phase};
+use crate::logger::Logger;
+
+use super::db::get_standalone_connection;
+
+/// Server errors of a `FLUSH TABLES WITH READ LOCK` the user may not run: access
+/// denied for the database or user, and the RELOAD privilege missing.
+const LOCK_DENIED: [u16; 3] = [1044, 1045, 1227];
+
+/// Source connections whose transactions all read the database as of the same moment.
+/// MySQL can't share one snapshot between sessions, so several are started together
+/// under a short global read lock (`FLUSH TABLES WITH READ LOCK`, needs RELOAD).
+/// They live outside the pool, so holding them never starves the workers.
+/// Managed servers often don't grant RELOAD; the source is then read without a
+/// shared snapshot.
+pub struct SnapshotConnections {
+    connections: Mutex<Vec<Conn>>,
+}
+
+impl SnapshotConnections {
+    /// `None` when the read lock is denied, so the caller reads without a snapshot.
+    pub fn open(db_config: &DbConfig, count: usize) -> CustomResult<Option<Self>> {
+        // A single transaction is consistent on its own
+        let mut lock_conn = match count > 1 {
+            true => Some(get_standalone_connection(db_config)?),
+            false => None,
+        };
+        let lock_query = "FLUSH TABLES WITH READ LOCK";
+        if let Some(lock_conn) = &mut lock_conn {
+            match lock_conn.query_drop(lock_query) {
+                Err(mysql::Error::MySqlError(err)) if LOCK_DENIED.contains(&err.code) => {
+                    Logger::new().warn(
+                        format!(
+                            "{} was denied ({}), reading the source without a consistent \
+                             snapshot; grant RELOAD or set consistent_snapshot = false",
+                            lock_query, err.message
+                        )
+                        .as_str(),
+                    );
+                    return Ok(None);
+                }
+                result => result.map_err(|err| CustomError::query(Phase::Data, lock_query, err))?,
+            }
+        }
+
+        let mut connections = Vec::with_capacity(count);
+        let started = (0..count).try_for_each(|_| {
+            let mut conn = get_standalone_connection(db_config)?;
+            for query in [
+                "SET SESSION TRANSACTION ISOLATION LEVEL REPEATABLE READ",
+                "START TRANSACTION WITH CONSISTENT SNAPSHOT, READ ONLY",
+            ] {
+                conn.query_drop(query)
+                    .map_err(|err| CustomError::query(Phase::Data, query, err))?;
+            }
+            connections.push(conn);
+            Ok(())
+        });
+
+        let unlock_query = "UNLOCK TABLES";
+        let unlocked = match &mut lock_conn {
+            Some(lock_conn) => lock_conn
+                .query_drop(unlock_query)
+                .map_err(|err| CustomError::query(Phase::Data, unlock_query, err)),
+            None => Ok(()),
+        };
+        started.and(unlocked)?;
+
+        Ok(Some(Self {
+            connections: Mutex::new(connections),
+        }))
+    }
+
+    /// Runs `read` on a free snapshot connection; there must be one per concurrent reader.
+    pub fn with<T>(&self, read: impl FnOnce(&mut Conn) -> CustomResult<T>) -> CustomResult<T> {
+        let conn = self.connections.lock().unwrap().pop();
+        let Some(mut conn) = conn else {
+            return Err(CustomError::Config(
+                "More concurrent readers than snapshot connections".to_string(),
+            ));
+        };
+        let result = read(&mut conn);
+        self.connections.lock().unwrap().push(conn);
+        result
+    }
+}
+
+impl Drop for SnapshotConnections {
+    fn drop(&mut self) {
+        for conn in self.connections.get_mut().unwrap().iter_mut() {
+            let _ = conn.query_drop("COMMIT");
+        }
+    }
+}
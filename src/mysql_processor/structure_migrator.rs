@@ -1,57 +1,100 @@
+use crate::checkpoint::Checkpoint;
 use crate::config::Config;
-use crate::error::{CustomError, CustomResult};
+use crate::error::{CustomError, CustomResult, Phase};
 use crate::logger::Logger;
-use crate::mysql_processor::db::get_connection;
+use crate::mysql_processor::db::{exec_drop, get_connections_pool, get_pooled_connection};
+use crate::parallel::run_blocking;
+use crate::report::Reporter;
 use crate::traits::StructureMigratorTrait;
-use mysql::PooledConn;
+use crate::watermark::STATE_TABLE;
 use mysql::{prelude::Queryable, Row};
+use mysql::{Pool, PooledConn};
+use std::time::Instant;
 pub struct StructureMigrator {
     pub config: Config,
 }
 
 impl StructureMigrator {
-    fn exec_no_output_statement(
+    pub fn exec_no_output_statement(
         &self,
         connection: &mut PooledConn,
+        phase: Phase,
         query: String,
     ) -> CustomResult<()> {
-        let result = connection.query_drop(query);
+        let result = exec_drop(connection, phase, &query);
 
         match result {
             Ok(_) => Ok(()),
-            Err(err) => {
-                println!("Error: {:?}", err);
-                Err(CustomError::QueryExecution)
-            }
+            Err(err) => Err(CustomError::query(phase, query, err)),
         }
     }
 
-    fn get_tables(&self, connection: &mut PooledConn) -> CustomResult<Vec<String>> {
+    pub fn get_tables(&self, connection: &mut PooledConn) -> CustomResult<Vec<String>> {
+        let query = "SHOW TABLES";
         let tables: Result<Vec<String>, mysql::Error> =
-            connection.query_map("SHOW TABLES", |table_name| table_name);
+            connection.query_map(query, |table_name| table_name);
 
         match tables {
             Ok(data) => Ok(data),
-            Err(_) => Err(CustomError::DbTableStructure),
+            Err(err) => Err(CustomError::query(Phase::Structure, query, err)),
         }
     }
 
-    fn get_create_table_ddl(
+    // FOREIGN_KEY_CHECKS is per session, so every worker connection turns it off itself.
+    fn create_table_in_pool(
+        &self,
+        source_pool: &Pool,
+        target_pool: &Pool,
+        table: &str,
+    ) -> CustomResult<()> {
+        let mut source_conn = get_pooled_connection(source_pool)?;
+        let mut target_conn = get_pooled_connection(target_pool)?;
+        self.exec_no_output_statement(
+            &mut target_conn,
+            Phase::Structure,
+            "SET FOREIGN_KEY_CHECKS = 0".to_string(),
+        )?;
+
+        let started = Instant::now();
+        let result = self.create_table(&mut source_conn, &mut target_conn, table);
+        Reporter::new().table_phase(table, Phase::Structure, started.elapsed());
+        let enabled = self.exec_no_output_statement(
+            &mut target_conn,
+            Phase::Structure,
+            "SET FOREIGN_KEY_CHECKS = 1".to_string(),
+        );
+
+        result.and(enabled)
+    }
+
+    pub fn create_table(
+        &self,
+        source_conn: &mut PooledConn,
+        target_conn: &mut PooledConn,
+        table: &str,
+    ) -> CustomResult<()> {
+        let create_table_query: String = self.get_create_table_ddl(source_conn, table)?;
+
+        self.exec_no_output_statement(target_conn, Phase::Structure, create_table_query)
+            .map_err(|err| err.with_table(table))
+    }
+
+    pub fn get_create_table_ddl(
         &self,
         connection: &mut PooledConn,
-        table: &String,
+        table: &str,
     ) -> CustomResult<String> {
         let ddl_query = format!("SHOW CREATE TABLE `{}`", table);
-        let row = connection
-            .query_first(ddl_query)
-            .map_err(|err| {
-                println!("Error: {:?}", err);
-                CustomError::QueryExecution
-            })
-            .and_then(|maybe_row| {
-                maybe_row.map_or(Err(CustomError::DbTableStructure), |row: Row| Ok(row))
-            })
-            .map_err(|_| CustomError::DbTableStructure)?;
+        let row: Row = connection
+            .query_first(&ddl_query)
+            .map_err(|err| CustomError::query(Phase::Structure, ddl_query, err).with_table(table))?
+            .ok_or_else(|| {
+                CustomError::structure(
+                    Phase::Structure,
+                    table,
+                    "SHOW CREATE TABLE returned no rows",
+                )
+            })?;
 
         let mut index: Option<usize> = None;
         let columns = row.columns_ref();
@@ -63,7 +106,11 @@ impl StructureMigrator {
         }
 
         let value = (match index {
-            None => Err(CustomError::DbTableStructure),
+            None => Err(CustomError::structure(
+                Phase::Structure,
+                table,
+                "`Create Table` column missing from SHOW CREATE TABLE output",
+            )),
             Some(value) => {
                 let query: String = row.get(value).expect("Value should be present in the Roo");
 
@@ -78,12 +125,16 @@ impl StructureMigrator {
 impl StructureMigratorTrait for StructureMigrator {
     async fn migrate(&self) -> CustomResult<()> {
         let logger = Logger::new();
+        let reporter = Reporter::new();
+        let checkpoint = Checkpoint::new();
         logger.info("Connecting to source database");
-        let mut source_conn = get_connection(&self.config.source)?;
+        let source_pool = get_connections_pool(&self.config.source)?;
+        let mut source_conn = get_pooled_connection(&source_pool)?;
         logger.info("Connected to source database");
 
         logger.info("Connecting to target database");
-        let mut target_conn = get_connection(&self.config.target)?;
+        let target_pool = get_connections_pool(&self.config.target)?;
+        let mut target_conn = get_pooled_connection(&target_pool)?;
         logger.info("Connected to target database");
 
         logger.info("Reading target tables");
@@ -91,15 +142,25 @@ impl StructureMigratorTrait for StructureMigrator {
         logger.info(format!("Read target tables: {}", target_tables.len()).as_str());
 
         logger.info("Disabling FK checks");
-        self.exec_no_output_statement(&mut target_conn, "SET FOREIGN_KEY_CHECKS = 0".to_string())?;
+        self.exec_no_output_statement(
+            &mut target_conn,
+            Phase::Structure,
+            "SET FOREIGN_KEY_CHECKS = 0".to_string(),
+        )?;
         logger.info("Disabled FK checks");
 
         logger.info("Dropping target tables");
         for table in &target_tables {
+            // Tables created by an earlier run are kept with --resume
+            if checkpoint.is_done(table, Phase::Structure) {
+                continue;
+            }
             self.exec_no_output_statement(
                 &mut target_conn,
+                Phase::Structure,
                 format!("DROP TABLE IF EXISTS `{}`", table),
-            )?;
+            )
+            .map_err(|err| err.with_table(table))?;
         }
         logger.info("Dropped target tables");
 
@@ -108,33 +169,82 @@ impl StructureMigratorTrait for StructureMigrator {
         logger.info(format!("Read remote tables: {}", source_tables.len()).as_str());
 
         let mut table_skipped: Vec<&str> = vec![];
-        let mut table_processed: Vec<&str> = vec![];
+        let mut table_processed: Vec<String> = vec![];
+        let mut table_failed: Vec<String> = vec![];
 
+        let mut tables_to_create: Vec<String> = vec![];
         for table in &source_tables {
-            if self.skip_table(table) || self.is_private_table(table) {
+            if let Some(reason) = self.skip_reason(table) {
+                reporter.skipped(table, reason);
                 table_skipped.push(table);
                 continue;
             }
-
-            let create_table_query: String = self.get_create_table_ddl(&mut source_conn, table)?;
-
-            self.exec_no_output_statement(&mut target_conn, create_table_query)?;
-
-            table_processed.push(table);
+            if checkpoint.is_done(table, Phase::Structure) {
+                logger.info(format!("Keeping table {} created by an earlier run", table).as_str());
+                continue;
+            }
+            tables_to_create.push(table.to_string());
         }
 
+        // With FK checks off tables can be created in any order, so all of them run
+        // side by side.
+        run_blocking(
+            &tables_to_create,
+            self.config.workers(),
+            |table| self.create_table_in_pool(&source_pool, &target_pool, table),
+            |table, result| {
+                match result {
+                    Ok(_) => {
+                        checkpoint.complete(table, Phase::Structure);
+                        table_processed.push(table.to_string());
+                    }
+                    Err(err) => {
+                        reporter.failure(table, Phase::Structure, &err);
+                        logger.error(format!("Failed to create table {}: {}", table, err).as_str());
+                        table_failed.push(table.to_string());
+                    }
+                }
+                logger.info(
+                    format!(
+                        "Finished table {} ({}/{})",
+                        table,
+                        table_processed.len() + table_failed.len(),
+                        tables_to_create.len()
+                    )
+                    .as_str(),
+                );
+                if self.config.run.fail_fast && !table_failed.is_empty() {
+                    logger.error("Stopping after first failure (--fail-fast)");
+                    return false;
+                }
+                true
+            },
+        );
+
         logger.info(format!("Skipped tables: {}", table_skipped.len()).as_str());
         logger.info(format!("Processed tables: {}", table_processed.len()).as_str());
 
         logger.info("Enabling FK checks");
-        self.exec_no_output_statement(&mut target_conn, "SET FOREIGN_KEY_CHECKS = 1".to_string())?;
+        self.exec_no_output_statement(
+            &mut target_conn,
+            Phase::Structure,
+            "SET FOREIGN_KEY_CHECKS = 1".to_string(),
+        )?;
         logger.info("Enabled FK checks");
 
+        if !table_failed.is_empty() {
+            return Err(CustomError::TablesFailed {
+                phase: Phase::Structure,
+                total: table_processed.len() + table_failed.len(),
+                failed: table_failed,
+            });
+        }
+
         Ok(())
     }
 
     fn is_private_table(&self, table_name: &str) -> bool {
-        let internal_tables = ["schema_migrations", "ar_internal_metadata"];
+        let internal_tables = ["schema_migrations", "ar_internal_metadata", STATE_TABLE];
 
         internal_tables.contains(&table_name)
     }
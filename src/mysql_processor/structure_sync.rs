@@ -0,0 +1,237 @@
+use std::collections::BTreeMap;
+
+use crate::config::Config;
+use crate::diff::{Change, TableDiff};
+use crate::error::{CustomError, CustomResult, Phase};
+use crate::logger::Logger;
+use crate::mysql_processor::db::get_connection;
+use crate::mysql_processor::schema_diff::SchemaDiffer;
+use crate::mysql_processor::structure_migrator::StructureMigrator;
+use crate::report::Reporter;
+use mysql::PooledConn;
+
+/// Column, key and foreign key clauses of a `SHOW CREATE TABLE` statement, by name.
+#[derive(Debug, Default)]
+struct TableDefinition {
+    columns: BTreeMap<String, String>,
+    indexes: BTreeMap<String, String>,
+    foreign_keys: BTreeMap<String, String>,
+}
+
+/// Brings the target tables in line with the source with `ALTER TABLE` statements
+/// built from the schema diff (`structure_mode = "sync"`). Tables, indexes and
+/// foreign keys that exist only in the target are kept, columns are dropped.
+pub struct StructureSync {
+    pub config: Config,
+}
+
+impl StructureSync {
+    pub fn migrate(&self) -> CustomResult<()> {
+        let logger = Logger::new();
+        let reporter = Reporter::new();
+        let struct_migrator = StructureMigrator {
+            config: self.config.clone(),
+        };
+
+        logger.info("Comparing source and target tables");
+        let differ = SchemaDiffer {
+            config: self.config.clone(),
+        };
+        let diff = differ.diff()?;
+        if diff.tables.is_empty() {
+            logger.info("Target tables match the source");
+            return Ok(());
+        }
+
+        let mut source_conn = get_connection(&self.config.source)?;
+        let mut target_conn = get_connection(&self.config.target)?;
+        struct_migrator.exec_no_output_statement(
+            &mut target_conn,
+            Phase::Structure,
+            "SET FOREIGN_KEY_CHECKS = 0".to_string(),
+        )?;
+
+        let mut total = 0;
+        let mut failures = vec![];
+        for table in &diff.tables {
+            if self.config.run.fail_fast && !failures.is_empty() {
+                logger.error("Stopping after first failure (--fail-fast)");
+                break;
+            }
+            let result = match table.status.as_str() {
+                "extra" => {
+                    logger.warn(
+                        format!(
+                            "Keeping table {} that only exists in the target",
+                            table.table
+                        )
+                        .as_str(),
+                    );
+                    continue;
+                }
+                "missing" => {
+                    logger.info(format!("Creating table {}", table.table).as_str());
+                    struct_migrator.create_table(&mut source_conn, &mut target_conn, &table.table)
+                }
+                _ => {
+                    logger.info(format!("Altering table {}", table.table).as_str());
+                    self.sync_table(&struct_migrator, &mut source_conn, &mut target_conn, table)
+                }
+            };
+            total += 1;
+            if let Err(err) = result {
+                reporter.failure(&table.table, Phase::Structure, &err);
+                logger.error(format!("Failed to sync table {}: {}", table.table, err).as_str());
+                failures.push(table.table.clone());
+            }
+        }
+
+        struct_migrator.exec_no_output_statement(
+            &mut target_conn,
+            Phase::Structure,
+            "SET FOREIGN_KEY_CHECKS = 1".to_string(),
+        )?;
+
+        if !failures.is_empty() {
+            return Err(CustomError::TablesFailed {
+                phase: Phase::Structure,
+                failed: failures,
+                total,
+            });
+        }
+
+        Ok(())
+    }
+
+    fn sync_table(
+        &self,
+        struct_migrator: &StructureMigrator,
+        source_conn: &mut PooledConn,
+        target_conn: &mut PooledConn,
+        table: &TableDiff,
+    ) -> CustomResult<()> {
+        let logger = Logger::new();
+        let ddl = struct_migrator.get_create_table_ddl(source_conn, &table.table)?;
+        let source = parse_create_table(&ddl);
+
+        // A foreign key can't be dropped and added under the same name in one ALTER
+        let mut drops = vec![];
+        let mut clauses = vec![];
+        for change in &table.columns {
+            match change {
+                Change::Missing { name, .. } => clauses.push(format!(
+                    "ADD COLUMN {}",
+                    clause(&source.columns, &table.table, "column", name)?
+                )),
+                Change::Changed { name, .. } => clauses.push(format!(
+                    "MODIFY COLUMN {}",
+                    clause(&source.columns, &table.table, "column", name)?
+                )),
+                Change::Extra { .. } => {}
+            }
+        }
+        for change in &table.indexes {
+            match change {
+                Change::Missing { name, .. } => clauses.push(format!(
+                    "ADD {}",
+                    clause(&source.indexes, &table.table, "index", name)?
+                )),
+                Change::Changed { name, .. } => {
+                    let index = clause(&source.indexes, &table.table, "index", name)?;
+                    clauses.push(match name.as_str() {
+                        "PRIMARY" => "DROP PRIMARY KEY".to_string(),
+                        _ => format!("DROP INDEX `{}`", name),
+                    });
+                    clauses.push(format!("ADD {}", index));
+                }
+                Change::Extra { name, .. } => logger.warn(
+                    format!("Keeping index {} that only exists in the target", name).as_str(),
+                ),
+            }
+        }
+        // Primary and unique keys are listed as constraints too; they change with
+        // their index above.
+        for change in &table.constraints {
+            match change {
+                Change::Missing { name, .. } if source.foreign_keys.contains_key(name) => {
+                    clauses.push(format!("ADD {}", source.foreign_keys[name]))
+                }
+                Change::Changed { name, .. } if source.foreign_keys.contains_key(name) => {
+                    drops.push(format!("DROP FOREIGN KEY `{}`", name));
+                    clauses.push(format!("ADD {}", source.foreign_keys[name]));
+                }
+                Change::Extra { name, .. } => logger.warn(
+                    format!("Keeping constraint {} that only exists in the target", name).as_str(),
+                ),
+                _ => {}
+            }
+        }
+        for change in &table.columns {
+            if let Change::Extra { name, .. } = change {
+                clauses.push(format!("DROP COLUMN `{}`", name));
+            }
+        }
+
+        for statement in [drops, clauses] {
+            if statement.is_empty() {
+                continue;
+            }
+            let statement = format!("ALTER TABLE `{}` {}", table.table, statement.join(", "));
+            logger.debug(&statement);
+            struct_migrator
+                .exec_no_output_statement(target_conn, Phase::Structure, statement)
+                .map_err(|err| err.with_table(&table.table))?;
+        }
+
+        Ok(())
+    }
+}
+
+// The diff reads names from `information_schema`, the clauses come from parsing
+// `SHOW CREATE TABLE`, so a name can be missing from the parsed ones.
+fn clause<'a>(
+    clauses: &'a BTreeMap<String, String>,
+    table: &str,
+    kind: &str,
+    name: &str,
+) -> CustomResult<&'a str> {
+    clauses.get(name).map(String::as_str).ok_or_else(|| {
+        CustomError::structure(
+            Phase::Structure,
+            table,
+            format!("{} {} not found in SHOW CREATE TABLE", kind, name),
+        )
+    })
+}
+
+fn parse_create_table(ddl: &str) -> TableDefinition {
+    let mut definition = TableDefinition::default();
+    // Every column, key and constraint is on a line of its own between the
+    // `CREATE TABLE` line and the closing `) ENGINE=...` line.
+    for line in ddl.lines().map(|line| line.trim().trim_end_matches(',')) {
+        if line.starts_with('`') {
+            if let Some(name) = quoted_name(line) {
+                definition.columns.insert(name, line.to_string());
+            }
+        } else if line.starts_with("PRIMARY KEY") {
+            definition
+                .indexes
+                .insert("PRIMARY".to_string(), line.to_string());
+        } else if line.starts_with("CONSTRAINT") && line.contains("FOREIGN KEY") {
+            if let Some(name) = quoted_name(line) {
+                definition.foreign_keys.insert(name, line.to_string());
+            }
+        } else if line.contains("KEY `") {
+            if let Some(name) = quoted_name(line) {
+                definition.indexes.insert(name, line.to_string());
+            }
+        }
+    }
+    definition
+}
+
+fn quoted_name(line: &str) -> Option<String> {
+    let (_, rest) = line.split_once('`')?;
+    let (name, _) = rest.split_once('`')?;
+    Some(name.to_string())
+}
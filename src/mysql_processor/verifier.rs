@@ -0,0 +1,230 @@
+use std::collections::BTreeMap;
+use std::time::Instant;
+
+use crate::config::Config;
+use crate::error::{CustomError, CustomResult, Phase};
+use crate::mysql_processor::db::{get_connections_pool, get_pooled_connection};
+use crate::parallel::run_blocking;
+use crate::report::Reporter;
+use crate::verify::{
+    compare, compare_columns, differing_buckets, differing_keys, Summary, BUCKET_DIGITS,
+};
+use mysql::prelude::Queryable;
+use mysql::{Pool, PooledConn};
+
+/// Compares every copied table of source and target by row count and checksum.
+pub struct Verifier {
+    pub config: Config,
+}
+
+impl Verifier {
+    pub fn verify(&self) -> CustomResult<()> {
+        let source_pool = get_connections_pool(&self.config.source)?;
+        let target_pool = get_connections_pool(&self.config.target)?;
+
+        let reporter = Reporter::new();
+        let tables = &self.config.tables.data_source;
+        let mut failed_tables = Vec::new();
+        run_blocking(
+            tables,
+            self.config.workers(),
+            |table| self.verify_table(&source_pool, &target_pool, table),
+            |table, result| {
+                if let Err(err) = result {
+                    reporter.failure(table, Phase::Verify, &err);
+                    println!("Table {} differs: {}", table, err);
+                    failed_tables.push(table.to_string());
+                }
+                true
+            },
+        );
+
+        if !failed_tables.is_empty() {
+            return Err(CustomError::TablesFailed {
+                phase: Phase::Verify,
+                failed: failed_tables,
+                total: tables.len(),
+            });
+        }
+        println!("All {} tables match", tables.len());
+        Ok(())
+    }
+
+    fn verify_table(
+        &self,
+        source_pool: &Pool,
+        target_pool: &Pool,
+        table: &str,
+    ) -> CustomResult<()> {
+        let mut source_conn = get_pooled_connection(source_pool)?;
+        let mut target_conn = get_pooled_connection(target_pool)?;
+
+        let started = Instant::now();
+        let result = self.compare_table(&mut source_conn, &mut target_conn, table);
+        Reporter::new().table_phase(table, Phase::Verify, started.elapsed());
+
+        result
+    }
+
+    fn compare_table(
+        &self,
+        source_conn: &mut PooledConn,
+        target_conn: &mut PooledConn,
+        table: &str,
+    ) -> CustomResult<()> {
+        let columns = self.get_columns(source_conn, table)?;
+        let target_columns = self.get_columns(target_conn, table)?;
+        if let Some(difference) = compare_columns(&columns, &target_columns) {
+            return Err(CustomError::structure(Phase::Verify, table, difference));
+        }
+
+        // Columns in name order; QUOTE keeps NULL apart from the string 'NULL'
+        let row = format!(
+            "MD5(CONCAT_WS(',', {}))",
+            columns
+                .iter()
+                .map(|column| format!("QUOTE(`{}`)", column))
+                .collect::<Vec<String>>()
+                .join(", ")
+        );
+        let source = self.summarize(source_conn, table, &row)?;
+        let target = self.summarize(target_conn, table, &row)?;
+
+        let Some(mut difference) = compare(&source, &target) else {
+            println!("Table {} matches: {} rows", table, source.rows);
+            return Ok(());
+        };
+        if self.config.verify.sample_keys > 0 {
+            let keys = self.sample_keys(source_conn, target_conn, table, &row)?;
+            difference = format!("{}; {}", difference, keys);
+        }
+
+        Err(CustomError::structure(Phase::Verify, table, difference))
+    }
+
+    fn get_columns(&self, connection: &mut PooledConn, table: &str) -> CustomResult<Vec<String>> {
+        let query = "SELECT COLUMN_NAME FROM information_schema.COLUMNS \
+                     WHERE TABLE_SCHEMA = DATABASE() AND TABLE_NAME = ? ORDER BY COLUMN_NAME";
+        connection
+            .exec(query, (table,))
+            .map_err(|err| CustomError::query(Phase::Verify, query, err).with_table(table))
+    }
+
+    fn checksum(&self, row: &str) -> String {
+        match self.config.verify.checksums {
+            true => format!(
+                "CAST(COALESCE(SUM(CAST(CONV(LEFT({}, 16), 16, 10) AS UNSIGNED)), 0) AS CHAR)",
+                row
+            ),
+            false => "NULL".to_string(),
+        }
+    }
+
+    fn summarize(
+        &self,
+        connection: &mut PooledConn,
+        table: &str,
+        row: &str,
+    ) -> CustomResult<Summary> {
+        let query = format!("SELECT COUNT(*), {} FROM `{}`", self.checksum(row), table);
+        let summary: Option<(i64, Option<String>)> =
+            connection.query_first(&query).map_err(|err| {
+                CustomError::query(Phase::Verify, query.as_str(), err).with_table(table)
+            })?;
+
+        let (rows, checksum) = summary.unwrap_or_default();
+        Ok(Summary { rows, checksum })
+    }
+
+    // Rows are spread over buckets by their key, so only the buckets that differ
+    // are read row by row.
+    fn sample_keys(
+        &self,
+        source_conn: &mut PooledConn,
+        target_conn: &mut PooledConn,
+        table: &str,
+        row: &str,
+    ) -> CustomResult<String> {
+        let query = "SELECT COLUMN_NAME FROM information_schema.KEY_COLUMN_USAGE \
+                     WHERE TABLE_SCHEMA = DATABASE() AND TABLE_NAME = ? \
+                     AND CONSTRAINT_NAME = 'PRIMARY' ORDER BY ORDINAL_POSITION";
+        let key_columns: Vec<String> = source_conn
+            .exec(query, (table,))
+            .map_err(|err| CustomError::query(Phase::Verify, query, err).with_table(table))?;
+        if key_columns.is_empty() {
+            return Ok("no primary key to sample".to_string());
+        }
+        let key = format!(
+            "CONCAT_WS(',', {})",
+            key_columns
+                .iter()
+                .map(|column| format!("`{}`", column))
+                .collect::<Vec<String>>()
+                .join(", ")
+        );
+
+        let source = self.summarize_buckets(source_conn, table, &key, row)?;
+        let target = self.summarize_buckets(target_conn, table, &key, row)?;
+
+        let limit = self.config.verify.sample_keys;
+        let mut keys = Vec::new();
+        for bucket in differing_buckets(&source, &target) {
+            let source_rows = self.read_bucket(source_conn, table, &key, row, &bucket)?;
+            let target_rows = self.read_bucket(target_conn, table, &key, row, &bucket)?;
+            keys.extend(differing_keys(
+                &source_rows,
+                &target_rows,
+                limit - keys.len(),
+            ));
+            if keys.len() >= limit {
+                break;
+            }
+        }
+
+        Ok(format!("differing keys: {}", keys.join(", ")))
+    }
+
+    fn summarize_buckets(
+        &self,
+        connection: &mut PooledConn,
+        table: &str,
+        key: &str,
+        row: &str,
+    ) -> CustomResult<BTreeMap<String, Summary>> {
+        let query = format!(
+            "SELECT LEFT(MD5({}), {}) AS bucket, COUNT(*), {} FROM `{}` GROUP BY bucket",
+            key,
+            BUCKET_DIGITS,
+            self.checksum(row),
+            table
+        );
+        let buckets: Vec<(String, i64, Option<String>)> =
+            connection.query(&query).map_err(|err| {
+                CustomError::query(Phase::Verify, query.as_str(), err).with_table(table)
+            })?;
+
+        Ok(buckets
+            .into_iter()
+            .map(|(bucket, rows, checksum)| (bucket, Summary { rows, checksum }))
+            .collect())
+    }
+
+    fn read_bucket(
+        &self,
+        connection: &mut PooledConn,
+        table: &str,
+        key: &str,
+        row: &str,
+        bucket: &str,
+    ) -> CustomResult<BTreeMap<String, String>> {
+        let query = format!(
+            "SELECT {0}, {1} FROM `{2}` WHERE LEFT(MD5({0}), {3}) = ?",
+            key, row, table, BUCKET_DIGITS
+        );
+        let rows: Vec<(String, String)> = connection.exec(&query, (bucket,)).map_err(|err| {
+            CustomError::query(Phase::Verify, query.as_str(), err).with_table(table)
+        })?;
+
+        Ok(rows.into_iter().collect())
+    }
+}
@@ -0,0 +1,78 @@
+use std::cell::Cell;
+use std::future::Future;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{mpsc, Mutex};
+use std::thread;
+
+use futures::{stream, StreamExt};
+
+/// Runs `task` for up to `workers` items (tables or chunks) at once on the current task
+/// and hands each result to `done` as it completes. Once `done` returns `false` no
+/// further item is started; running ones are finished, never cancelled half-way.
+pub async fn run_async<'a, I, T, F, Fut, D>(items: &'a [I], workers: usize, task: F, mut done: D)
+where
+    F: Fn(&'a I) -> Fut,
+    Fut: Future<Output = T>,
+    D: FnMut(&'a I, T) -> bool,
+{
+    let stopped = Cell::new(false);
+    let mut results = stream::iter(items)
+        .map(|item| {
+            let stopped = &stopped;
+            let task = &task;
+            async move {
+                if stopped.get() {
+                    return (item, None);
+                }
+                (item, Some(task(item).await))
+            }
+        })
+        .buffer_unordered(workers.max(1));
+
+    while let Some((item, result)) = results.next().await {
+        if let Some(result) = result {
+            if !done(item, result) {
+                stopped.set(true);
+            }
+        }
+    }
+}
+
+/// Blocking counterpart of `run_async` for the synchronous mysql driver: `task` runs on
+/// `workers` scoped threads while `done` runs on the calling thread.
+pub fn run_blocking<'a, I, T, F, D>(items: &'a [I], workers: usize, task: F, mut done: D)
+where
+    I: Sync,
+    T: Send,
+    F: Fn(&'a I) -> T + Sync,
+    D: FnMut(&'a I, T) -> bool,
+{
+    let queue = Mutex::new(items.iter());
+    let stopped = AtomicBool::new(false);
+
+    thread::scope(|scope| {
+        let (sender, receiver) = mpsc::channel();
+        for _ in 0..workers.clamp(1, items.len().max(1)) {
+            let sender = sender.clone();
+            let (queue, stopped, task) = (&queue, &stopped, &task);
+            scope.spawn(move || loop {
+                let Some(item) = queue.lock().unwrap().next() else {
+                    break;
+                };
+                if stopped.load(Ordering::SeqCst) {
+                    break;
+                }
+                if sender.send((item, task(item))).is_err() {
+                    break;
+                }
+            });
+        }
+        drop(sender);
+
+        for (item, result) in receiver {
+            if !done(item, result) {
+                stopped.store(true, Ordering::SeqCst);
+            }
+        }
+    });
+}
@@ -0,0 +1,91 @@
+use std::fs;
+use std::sync::Mutex;
+
+use crate::error::Phase;
+use crate::logger::Logger;
+
+static PLAN: Mutex<Option<PlanState>> = Mutex::new(None);
+
+struct PlanState {
+    path: Option<String>,
+    steps: Vec<Step>,
+}
+
+enum Step {
+    Statement(Phase, String),
+    Note(Phase, String),
+}
+
+/// Statements collected instead of executed when running with `--dry-run`.
+#[derive(Debug)]
+pub struct Plan {}
+
+impl Plan {
+    pub fn new() -> Self {
+        Self {}
+    }
+
+    /// Enables dry-run mode; `path` is the optional `.sql` file the plan is written to.
+    pub fn init(path: Option<String>) {
+        *PLAN.lock().unwrap() = Some(PlanState {
+            path,
+            steps: Vec::new(),
+        });
+    }
+
+    pub fn is_dry_run(&self) -> bool {
+        PLAN.lock().unwrap().is_some()
+    }
+
+    /// Records a statement that would run against the target.
+    pub fn statement(&self, phase: Phase, statement: &str) {
+        if let Some(state) = PLAN.lock().unwrap().as_mut() {
+            state
+                .steps
+                .push(Step::Statement(phase, statement.trim().to_string()));
+        }
+    }
+
+    /// Records a step that has no single statement, e.g. the rows a table would receive.
+    pub fn note(&self, phase: Phase, note: &str) {
+        if let Some(state) = PLAN.lock().unwrap().as_mut() {
+            state.steps.push(Step::Note(phase, note.to_string()));
+        }
+    }
+
+    /// Prints the collected plan and writes it to the plan file, if any.
+    pub fn finish(&self) {
+        let logger = Logger::new();
+        let Some(state) = PLAN.lock().unwrap().take() else {
+            return;
+        };
+
+        let mut sql = String::from("-- db_copy dry run: nothing below was executed\n");
+        let mut current_phase = None;
+        for step in &state.steps {
+            let (phase, line) = match step {
+                Step::Statement(phase, statement) if statement.ends_with(';') => {
+                    (phase, statement.clone())
+                }
+                Step::Statement(phase, statement) => (phase, format!("{};", statement)),
+                Step::Note(phase, note) => (phase, format!("-- {}", note)),
+            };
+            if current_phase != Some(*phase) {
+                sql.push_str(format!("\n-- [{}]\n", phase).as_str());
+                current_phase = Some(*phase);
+            }
+            sql.push_str(&line);
+            sql.push('\n');
+        }
+
+        println!("{}", sql);
+
+        if let Some(path) = &state.path {
+            match fs::write(path, &sql) {
+                Ok(()) => logger.info(format!("Wrote migration plan to {}", path).as_str()),
+                Err(err) => logger
+                    .error(format!("Failed to write migration plan {}: {}", path, err).as_str()),
+            }
+        }
+    }
+}
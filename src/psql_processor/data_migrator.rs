@@ -1,9 +1,51 @@
-use sqlx::{postgres::PgRow, Pool, Postgres, Row};
+use std::collections::{BTreeMap, BTreeSet};
+use std::time::Instant;
+
+use futures::TryStreamExt;
+use sqlx::postgres::{PgConnection, PgRow};
+use sqlx::{Pool, Postgres, Row, Transaction};
 
 use crate::{
-    config::Config, error::CustomError, logger::Logger, psql_processor::db::get_connections_pool,
+    checkpoint::Checkpoint,
+    chunk::{split, Chunk},
+    config::{Config, LoadMode, TransferMode},
+    dependency::{sort_tables, LoadOrder},
+    error::{CustomError, Phase},
+    logger::Logger,
+    parallel::run_async,
+    plan::Plan,
+    psql_processor::db::{execute, get_connections_pool, get_references},
+    psql_processor::snapshot::Snapshot,
+    psql_processor::value_mapper::ValueKind,
+    report::Reporter,
+    watermark::{above, Increment, STATE_TABLE},
     CustomResult,
 };
+
+/// Temporary table of the target session the source keys of a `delete-missing` table
+/// are staged in.
+const KEYS_TABLE: &str = "db_copy_keys";
+
+/// Unit of work for the load workers: a whole table, or one chunk of it.
+struct Job {
+    table: String,
+    chunk: Option<Chunk>,
+}
+
+/// Table rows are written to with multi-row INSERTs; `on_conflict` makes them upserts.
+struct Insert<'a> {
+    table: &'a str,
+    columns: &'a [(String, String, String)],
+    on_conflict: Option<&'a str>,
+}
+
+/// Rows and bytes written so far, reported once it is known they are kept.
+#[derive(Default)]
+struct Progress {
+    rows: u64,
+    bytes: u64,
+}
+
 pub struct DataMigrator {
     pub config: Config,
     pub target_schema: String,
@@ -11,6 +53,8 @@ pub struct DataMigrator {
     pub source_conn: Pool<Postgres>,
     pub target_conn: Pool<Postgres>,
     pub logger: Logger,
+    pub reporter: Reporter,
+    pub plan: Plan,
 }
 
 impl DataMigrator {
@@ -34,18 +78,180 @@ impl DataMigrator {
             source_conn,
             target_conn,
             logger,
+            reporter: Reporter::new(),
+            plan: Plan::new(),
         })
     }
 
     pub async fn migrate(&self) -> CustomResult<()> {
+        let order = self.get_load_order().await?;
+        let shared = self.export_snapshot().await?;
+        let checkpoint = Checkpoint::new();
+        let copied = checkpoint.prepare_data(&order);
         let mut failed_tables = Vec::new();
         let mut success_tables = Vec::new();
-        for table in &self.config.tables.data_source {
-            let result = self.migrate_table(table).await;
-            if result.is_err() {
-                failed_tables.push(table.to_string());
-            } else {
-                success_tables.push(table.to_string());
+        // Tables --fail-fast stopped before don't count towards the exit status
+        let mut attempted = BTreeSet::new();
+
+        let incremental = |table: &String| self.config.tables.settings(table).watermark.is_some();
+        if order.tables.iter().any(incremental) {
+            self.create_state_table().await?;
+        }
+
+        // TRUNCATE ... CASCADE empties every table referencing the truncated one
+        let kept: BTreeSet<String> = order
+            .tables
+            .iter()
+            .filter(|table| !self.config.tables.settings(table).truncates())
+            .cloned()
+            .collect();
+
+        // Children are emptied before their parents and loaded after them
+        for table in order.tables.iter().rev() {
+            if copied.contains(table) || checkpoint.has_copied_chunks(table) {
+                self.logger.info(
+                    format!("Keeping rows of table {} copied by an earlier run", table).as_str(),
+                );
+                continue;
+            }
+            let settings = self.config.tables.settings(table);
+            if !settings.truncates() {
+                let mode = match settings.watermark {
+                    Some(_) => "incremental".to_string(),
+                    None => settings.load.to_string(),
+                };
+                self.logger
+                    .info(format!("Keeping rows of table {} ({})", table, mode).as_str());
+                continue;
+            }
+            self.logger
+                .debug(format!("Truncating data from table: {}", table).as_str());
+            attempted.insert(table.to_string());
+            let started = Instant::now();
+            let result = self.truncate_table(table, &order, &kept).await;
+            self.reporter
+                .table_phase(table, Phase::Data, started.elapsed());
+            if let Err(err) = result {
+                self.record_failure(table, &err, &mut failed_tables);
+                if self.config.run.fail_fast {
+                    break;
+                }
+            }
+        }
+
+        let workers = self.config.workers();
+        self.logger
+            .info(format!("Loading tables with {} workers", workers).as_str());
+
+        // A level only references earlier levels, so its tables load side by side
+        let total = order.tables.len() - copied.len();
+        for level in &order.levels {
+            if self.config.run.fail_fast && !failed_tables.is_empty() {
+                self.logger
+                    .error("Stopping after first failure (--fail-fast)");
+                break;
+            }
+            let pending: Vec<String> = level
+                .iter()
+                .filter(|table| !failed_tables.contains(table) && !copied.contains(*table))
+                .cloned()
+                .collect();
+            attempted.extend(pending.iter().cloned());
+
+            // Chunks of a table are separate jobs sharing the same workers
+            let mut jobs = Vec::new();
+            let mut snapshots = BTreeMap::new();
+            for table in &pending {
+                let cyclic = order.cyclic.contains(table);
+                match self
+                    .plan_jobs(table, cyclic, shared.as_ref(), &mut snapshots)
+                    .await
+                {
+                    Ok(table_jobs) => jobs.extend(table_jobs),
+                    Err(err) => self.record_failure(table, &err, &mut failed_tables),
+                }
+            }
+
+            let mut remaining: BTreeMap<&str, usize> = BTreeMap::new();
+            for job in &jobs {
+                *remaining.entry(job.table.as_str()).or_default() += 1;
+            }
+            let planned = remaining.clone();
+            let mut errors: BTreeMap<&str, CustomError> = BTreeMap::new();
+
+            run_async(
+                &jobs,
+                workers,
+                |job| {
+                    let snapshot = shared.as_ref().or_else(|| snapshots.get(&job.table));
+                    self.run_job(job, &order, snapshot)
+                },
+                |job, result| {
+                    let table = job.table.as_str();
+                    if let Err(err) = result {
+                        errors.entry(table).or_insert(err);
+                    }
+                    let left = remaining.get_mut(table).unwrap();
+                    *left -= 1;
+                    if *left == 0 {
+                        match errors.remove(table) {
+                            None => {
+                                checkpoint.complete(table, Phase::Data);
+                                success_tables.push(table.to_string());
+                            }
+                            Some(err) => self.record_failure(table, &err, &mut failed_tables),
+                        }
+                        self.logger.info(
+                            format!(
+                                "Finished table {} ({}/{})",
+                                table,
+                                success_tables.len() + failed_tables.len(),
+                                total
+                            )
+                            .as_str(),
+                        );
+                    }
+                    !self.config.run.fail_fast || (failed_tables.is_empty() && errors.is_empty())
+                },
+            )
+            .await;
+
+            // --fail-fast can leave tables with only some of their chunks copied
+            for (table, left) in remaining {
+                if left == 0 || (left == planned[table] && !errors.contains_key(table)) {
+                    continue;
+                }
+                let err = errors.remove(table).unwrap_or_else(|| {
+                    CustomError::structure(
+                        Phase::Data,
+                        table,
+                        format!(
+                            "stopped with {} of {} chunks not copied",
+                            left, planned[table]
+                        ),
+                    )
+                });
+                self.record_failure(table, &err, &mut failed_tables);
+            }
+        }
+
+        // Children lose their rows before the parents they reference
+        for table in order.tables.iter().rev() {
+            if self.config.run.fail_fast && !failed_tables.is_empty() {
+                break;
+            }
+            if self.config.tables.settings(table).load != LoadMode::DeleteMissing
+                || failed_tables.contains(table)
+            {
+                continue;
+            }
+            let started = Instant::now();
+            let result = self.delete_missing(table, shared.as_ref()).await;
+            self.reporter
+                .table_phase(table, Phase::Data, started.elapsed());
+            if let Err(err) = result {
+                success_tables.retain(|success| success != table);
+                self.record_failure(table, &err, &mut failed_tables);
             }
         }
 
@@ -53,16 +259,405 @@ impl DataMigrator {
             .info(format!("Failed tables: {:?}", failed_tables).as_str());
         self.logger
             .info(format!("Success tables: {:?}", success_tables).as_str());
+
+        if !failed_tables.is_empty() {
+            return Err(CustomError::TablesFailed {
+                phase: Phase::Data,
+                failed: failed_tables,
+                total: attempted.len(),
+            });
+        }
         Ok(())
     }
 
-    async fn migrate_table(&self, table: &str) -> CustomResult<()> {
+    fn record_failure(&self, table: &str, err: &CustomError, failed_tables: &mut Vec<String>) {
+        self.reporter.failure(table, Phase::Data, err);
+        self.logger
+            .error(format!("Failed to migrate data for table {}: {}", table, err).as_str());
+        failed_tables.push(table.to_string());
+    }
+
+    async fn get_load_order(&self) -> CustomResult<LoadOrder> {
+        let references = get_references(&self.source_conn, Phase::Data, &self.source_schema)
+            .await
+            .map_err(|err| {
+                self.logger
+                    .error(format!("Failed to get foreign keys: {}", err).as_str());
+                err
+            })?;
+
+        let order = sort_tables(&self.config.tables.data_source, &references);
         self.logger
-            .debug(format!("Truncating data from table: {}", table).as_str());
-        self.truncate_table(table).await?;
+            .info(format!("Table load order: {:?}", order.tables).as_str());
+        if !order.cyclic.is_empty() {
+            self.logger.warn(
+                format!(
+                    "Tables in a foreign key cycle, loaded with triggers disabled: {:?}",
+                    order.cyclic
+                )
+                .as_str(),
+            );
+        }
+        Ok(order)
+    }
+
+    // Workers read from one snapshot for the whole run, so tables copied minutes apart
+    // still agree on which rows exist.
+    async fn export_snapshot(&self) -> CustomResult<Option<Snapshot>> {
+        if !self.config.technology.consistent_snapshot {
+            return Ok(None);
+        }
+
+        let snapshot = Snapshot::export(&self.config.source).await?;
         self.logger
-            .debug(format!("Truncated data from table: {}", table).as_str());
+            .info(format!("Reading the source from snapshot {}", snapshot.id).as_str());
+        Ok(Some(snapshot))
+    }
 
+    async fn plan_jobs(
+        &self,
+        table: &str,
+        cyclic: bool,
+        shared: Option<&Snapshot>,
+        snapshots: &mut BTreeMap<String, Snapshot>,
+    ) -> CustomResult<Vec<Job>> {
+        let to_jobs = |chunks: Vec<Chunk>| {
+            chunks
+                .into_iter()
+                .map(|chunk| Job {
+                    table: table.to_string(),
+                    chunk: Some(chunk),
+                })
+                .collect()
+        };
+        // Ranges of an interrupted run are kept, whatever the table holds by now
+        if let Some(chunks) = Checkpoint::new().remaining_chunks(table) {
+            self.logger.info(
+                format!("Resuming table {} with {} chunks left", table, chunks.len()).as_str(),
+            );
+            return Ok(to_jobs(chunks));
+        }
+
+        let settings = self.config.tables.settings(table);
+        let whole = vec![Job {
+            table: table.to_string(),
+            chunk: None,
+        }];
+        if settings.chunks <= 1 {
+            return Ok(whole);
+        }
+        if settings.upserts() {
+            self.logger
+                .warn(format!("Table {} is upserted, copying it in one piece", table).as_str());
+            return Ok(whole);
+        }
+        if cyclic {
+            // Triggers are switched per table, so a cyclic table is loaded in one go
+            self.logger.warn(
+                format!(
+                    "Table {} is in a foreign key cycle, copying it in one piece",
+                    table
+                )
+                .as_str(),
+            );
+            return Ok(whole);
+        }
+
+        let snapshot = match shared.is_none() && settings.snapshot {
+            true => Some(Snapshot::export(&self.config.source).await?),
+            false => None,
+        };
+        let chunks = self
+            .split_table(table, settings.chunks, shared.or(snapshot.as_ref()))
+            .await?;
+        self.logger
+            .info(format!("Copying table {} in {} chunks", table, chunks.len()).as_str());
+        Checkpoint::new().plan_chunks(table, &chunks);
+        if let Some(snapshot) = snapshot {
+            snapshots.insert(table.to_string(), snapshot);
+        }
+
+        Ok(to_jobs(chunks))
+    }
+
+    async fn run_job(
+        &self,
+        job: &Job,
+        order: &LoadOrder,
+        snapshot: Option<&Snapshot>,
+    ) -> CustomResult<()> {
+        match &job.chunk {
+            Some(chunk) => self.copy_chunk(&job.table, chunk, snapshot).await,
+            None => {
+                self.load_table(&job.table, order.cyclic.contains(&job.table), snapshot)
+                    .await
+            }
+        }
+    }
+
+    // Ranges of a single-column integer primary key, or of heap pages when there is none.
+    async fn split_table(
+        &self,
+        table: &str,
+        count: usize,
+        snapshot: Option<&Snapshot>,
+    ) -> CustomResult<Vec<Chunk>> {
+        let mut source = self.begin_source(snapshot).await?;
+        let qualified = format!("\"{}\".\"{}\"", self.source_schema, table);
+
+        let key_query = r#"
+            SELECT a.attname
+            FROM pg_index i
+            JOIN pg_attribute a ON a.attrelid = i.indrelid AND a.attnum = ANY(i.indkey)
+            WHERE i.indrelid = to_regclass($1)
+            AND i.indisprimary
+            AND i.indnatts = 1
+            AND a.atttypid IN ('int2'::regtype, 'int4'::regtype, 'int8'::regtype);
+        "#;
+        let key: Option<String> = sqlx::query_scalar(key_query)
+            .bind(&qualified)
+            .fetch_optional(&mut *source)
+            .await
+            .map_err(|e| CustomError::query(Phase::Data, key_query, e).with_table(table))?;
+
+        if let Some(key) = key {
+            let column = format!("\"{}\"", key);
+            let range_query = format!(
+                "SELECT MIN({0})::bigint, MAX({0})::bigint FROM {1}.{2}",
+                column, self.source_schema, table
+            );
+            let (min, max): (Option<i64>, Option<i64>) = sqlx::query_as(&range_query)
+                .fetch_one(&mut *source)
+                .await
+                .map_err(|e| CustomError::query(Phase::Data, &range_query, e).with_table(table))?;
+
+            return Ok(split(min.zip(max), count, &column, |value| {
+                value.to_string()
+            }));
+        }
+
+        let pages_query =
+            "SELECT pg_relation_size(to_regclass($1)) / current_setting('block_size')::bigint";
+        let pages: i64 = sqlx::query_scalar(pages_query)
+            .bind(&qualified)
+            .fetch_one(&mut *source)
+            .await
+            .map_err(|e| CustomError::query(Phase::Data, pages_query, e).with_table(table))?;
+        self.logger.debug(
+            format!(
+                "Table {} has no integer primary key, splitting its {} pages",
+                table, pages
+            )
+            .as_str(),
+        );
+
+        Ok(split(
+            (pages > 0).then_some((0, pages - 1)),
+            count,
+            "ctid",
+            |page| format!("'({},0)'::tid", page),
+        ))
+    }
+
+    async fn begin_source(
+        &self,
+        snapshot: Option<&Snapshot>,
+    ) -> CustomResult<Transaction<'static, Postgres>> {
+        match snapshot {
+            Some(snapshot) => snapshot.begin(&self.source_conn).await,
+            None => self
+                .source_conn
+                .begin()
+                .await
+                .map_err(CustomError::connection),
+        }
+    }
+
+    async fn copy_chunk(
+        &self,
+        table: &str,
+        chunk: &Chunk,
+        snapshot: Option<&Snapshot>,
+    ) -> CustomResult<()> {
+        let columns = self.get_table_columns(&self.source_schema, table).await?;
+        let mut transfer = self.config.tables.settings(table).transfer;
+        if self.plan.is_dry_run() {
+            return self
+                .plan_insert(table, &columns, transfer, Some(chunk))
+                .await;
+        }
+
+        let started = Instant::now();
+        let retries = self.config.technology.chunk_retries;
+        let mut attempt = 0;
+        let result = loop {
+            match self
+                .try_copy_chunk(table, chunk, &columns, transfer, snapshot)
+                .await
+            {
+                Ok(_) => break Ok(()),
+                Err(err) if transfer == TransferMode::Copy => {
+                    self.logger.warn(
+                        format!(
+                            "Binary COPY failed for {} of table {}, retrying as CSV: {}",
+                            chunk, table, err
+                        )
+                        .as_str(),
+                    );
+                    transfer = TransferMode::CopyCsv;
+                }
+                Err(err) if attempt < retries => {
+                    attempt += 1;
+                    self.logger.warn(
+                        format!(
+                            "Failed to copy {} of table {}, retrying ({}/{}): {}",
+                            chunk, table, attempt, retries, err
+                        )
+                        .as_str(),
+                    );
+                }
+                Err(err) => break Err(err),
+            }
+        };
+        self.reporter
+            .table_phase(table, Phase::Data, started.elapsed());
+
+        result
+    }
+
+    async fn try_copy_chunk(
+        &self,
+        table: &str,
+        chunk: &Chunk,
+        columns: &[(String, String, String)],
+        transfer: TransferMode,
+        snapshot: Option<&Snapshot>,
+    ) -> CustomResult<()> {
+        let mut source = self.begin_source(snapshot).await?;
+        // Nothing of a failed chunk is kept, so it can simply be copied again
+        let mut target = self
+            .target_conn
+            .begin()
+            .await
+            .map_err(CustomError::connection)?;
+
+        let progress = match transfer {
+            TransferMode::Insert => {
+                let select_query = format!(
+                    "{} WHERE {}",
+                    self.get_select_string(&self.source_schema, table).await?,
+                    chunk.condition
+                );
+                let mut progress = Progress::default();
+                let insert = Insert {
+                    table,
+                    columns,
+                    on_conflict: None,
+                };
+                self.insert_rows(
+                    &mut source,
+                    &mut target,
+                    &insert,
+                    &select_query,
+                    &mut progress,
+                )
+                .await?;
+                progress
+            }
+            TransferMode::Copy | TransferMode::CopyCsv => {
+                let format = match transfer {
+                    TransferMode::Copy => "binary",
+                    _ => "csv",
+                };
+                self.copy_rows(
+                    &mut source,
+                    &mut target,
+                    table,
+                    columns,
+                    format,
+                    Some(&chunk.condition),
+                )
+                .await?
+            }
+        };
+
+        target
+            .commit()
+            .await
+            .map_err(|e| CustomError::query(Phase::Data, "COMMIT", e).with_table(table))?;
+        Checkpoint::new().chunk_copied(table, chunk);
+        self.reporter
+            .rows(table, progress.rows, progress.rows, progress.bytes);
+
+        self.logger.debug(
+            format!(
+                "Copied {} of table {}: {} rows",
+                chunk, table, progress.rows
+            )
+            .as_str(),
+        );
+        Ok(())
+    }
+
+    async fn load_table(
+        &self,
+        table: &str,
+        cyclic: bool,
+        snapshot: Option<&Snapshot>,
+    ) -> CustomResult<()> {
+        let started = Instant::now();
+        let result = if cyclic {
+            self.migrate_cyclic_table(table, snapshot).await
+        } else {
+            self.migrate_table(table, snapshot).await
+        };
+        self.reporter
+            .table_phase(table, Phase::Data, started.elapsed());
+
+        result
+    }
+
+    // Postgres enforces foreign keys with triggers, so with them disabled the rows
+    // of a reference cycle can arrive in any order. Loaded rows are not re-checked.
+    async fn migrate_cyclic_table(
+        &self,
+        table: &str,
+        snapshot: Option<&Snapshot>,
+    ) -> CustomResult<()> {
+        self.set_triggers(table, "DISABLE").await?;
+        let result = self.migrate_table(table, snapshot).await;
+        let enabled = self.set_triggers(table, "ENABLE").await;
+
+        result.and(enabled)
+    }
+
+    async fn set_triggers(&self, table: &str, action: &str) -> CustomResult<()> {
+        let query = format!(
+            "ALTER TABLE {}.{} {} TRIGGER ALL",
+            self.target_schema, table, action
+        );
+        execute(&self.target_conn, Phase::Data, &query)
+            .await
+            .map_err(|e| {
+                self.logger.error(e.to_string().as_str());
+                // Foreign keys are system triggers, which only a superuser can switch
+                let denied = e.as_database_error().and_then(|e| e.code());
+                if denied.is_some_and(|code| code == "42501") {
+                    return CustomError::structure(
+                        Phase::Data,
+                        table,
+                        format!(
+                            "{} TRIGGER ALL on a table in a foreign key cycle needs a superuser \
+                             target user: {}",
+                            action, e
+                        ),
+                    );
+                }
+                CustomError::query(Phase::Data, &query, e).with_table(table)
+            })
+    }
+
+    async fn migrate_table(&self, table: &str, snapshot: Option<&Snapshot>) -> CustomResult<()> {
         self.logger
             .debug(format!("Migrating data for table: {}", table).as_str());
 
@@ -70,77 +665,692 @@ impl DataMigrator {
         let select_query = self.get_select_string(&self.source_schema, table).await?;
 
         // Get column names for proper value extraction
-        let raw_columns = self.get_table_columns(&self.source_schema, table).await?;
+        let columns = self.get_table_columns(&self.source_schema, table).await?;
+
+        let settings = self.config.tables.settings(table);
+        if let Some(column) = &settings.watermark {
+            return self
+                .migrate_increment(table, column, &select_query, &columns, snapshot)
+                .await;
+        }
+
+        let transfer = settings.transfer;
+        if self.plan.is_dry_run() {
+            return self.plan_insert(table, &columns, transfer, None).await;
+        }
+        if settings.upserts() {
+            return self
+                .upsert_rows(table, &select_query, &columns, snapshot)
+                .await;
+        }
+
+        self.transfer_rows(table, &select_query, &columns, transfer, snapshot)
+            .await
+    }
+
+    // Rows above the stored watermark are upserted, so rows changed since the last run
+    // are updated in place and a failed run can simply be repeated.
+    async fn migrate_increment(
+        &self,
+        table: &str,
+        column: &str,
+        select_query: &str,
+        columns: &[(String, String, String)],
+        snapshot: Option<&Snapshot>,
+    ) -> CustomResult<()> {
+        let increment = self.get_increment(table, column, snapshot).await?;
+        if self.plan.is_dry_run() {
+            let count = self.count_rows(table, Some(&increment.condition)).await?;
+            self.plan.note(
+                Phase::Data,
+                format!(
+                    "UPSERT INTO {}.{} ({}) WHERE {}: {} rows",
+                    self.target_schema,
+                    table,
+                    self.get_column_list(columns)?.join(", "),
+                    increment.condition,
+                    count
+                )
+                .as_str(),
+            );
+            return Ok(());
+        }
+        let Some(next) = &increment.next else {
+            self.logger
+                .debug(format!("No data to migrate for table: {}", table).as_str());
+            return Ok(());
+        };
+        self.logger.debug(
+            format!(
+                "Copying table {} from {} {:?} to {}",
+                table, column, increment.last, next
+            )
+            .as_str(),
+        );
+
+        let select_query = format!("{} WHERE {}", select_query, increment.condition);
+        self.upsert_rows(table, &select_query, columns, snapshot)
+            .await?;
+
+        self.store_watermark(table, column, next).await
+    }
+
+    async fn upsert_rows(
+        &self,
+        table: &str,
+        select_query: &str,
+        columns: &[(String, String, String)],
+        snapshot: Option<&Snapshot>,
+    ) -> CustomResult<()> {
+        if self.config.tables.settings(table).transfer != TransferMode::Insert {
+            self.logger.warn(
+                format!(
+                    "COPY can't update existing rows, copying table {} with INSERT",
+                    table
+                )
+                .as_str(),
+            );
+        }
+
+        let on_conflict = self.get_on_conflict(table, columns).await?;
+        let mut source = self.begin_source(snapshot).await?;
+        let mut target = self
+            .target_conn
+            .acquire()
+            .await
+            .map_err(CustomError::connection)?;
+
+        let insert = Insert {
+            table,
+            columns,
+            on_conflict: Some(&on_conflict),
+        };
+        let mut progress = Progress::default();
+        let result = self
+            .insert_rows(
+                &mut source,
+                &mut target,
+                &insert,
+                select_query,
+                &mut progress,
+            )
+            .await;
+        // Batches are committed one by one, so rows written before a failure stay
+        self.reporter
+            .rows(table, progress.rows, progress.rows, progress.bytes);
+        result
+    }
 
-        let columns = raw_columns
+    async fn get_increment(
+        &self,
+        table: &str,
+        column: &str,
+        snapshot: Option<&Snapshot>,
+    ) -> CustomResult<Increment> {
+        let expression = format!("\"{}\"", column);
+        let literal = |value: &str| ValueKind::Text.literal(Some(value));
+        let last = self.read_watermark(table, column).await?;
+        let max_query = format!(
+            "SELECT MAX({})::text FROM {}.{} WHERE {}",
+            expression,
+            self.source_schema,
+            table,
+            above(&expression, last.as_deref(), literal)
+        );
+        let mut source = self.begin_source(snapshot).await?;
+        let next: Option<String> = sqlx::query_scalar(&max_query)
+            .fetch_one(&mut *source)
+            .await
+            .map_err(|e| CustomError::query(Phase::Data, &max_query, e).with_table(table))?;
+
+        Ok(Increment::new(&expression, last, next, literal))
+    }
+
+    async fn get_on_conflict(
+        &self,
+        table: &str,
+        columns: &[(String, String, String)],
+    ) -> CustomResult<String> {
+        let key = self.get_key(table).await?;
+        let updates: Vec<String> = columns
             .iter()
-            .filter(|(name, _, _)| name != "id") // Exclude ID column
-            .map(|(name, data_type, is_nullable)| {
-                (name.clone(), data_type.clone(), is_nullable.clone())
+            .filter(|(name, _, _)| !key.contains(name))
+            .map(|(name, _, _)| format!("\"{0}\" = EXCLUDED.\"{0}\"", name))
+            .collect();
+        let key: Vec<String> = key.iter().map(|name| format!("\"{}\"", name)).collect();
+
+        Ok(match updates.is_empty() {
+            true => format!("ON CONFLICT ({}) DO NOTHING", key.join(", ")),
+            false => format!(
+                "ON CONFLICT ({}) DO UPDATE SET {}",
+                key.join(", "),
+                updates.join(", ")
+            ),
+        })
+    }
+
+    // The configured `key`, or the primary key of the target table.
+    async fn get_key(&self, table: &str) -> CustomResult<Vec<String>> {
+        let settings = self.config.tables.settings(table);
+        if !settings.key.is_empty() {
+            return Ok(settings.key);
+        }
+
+        let key_query = r#"
+            SELECT a.attname::text
+            FROM pg_index i
+            JOIN pg_attribute a ON a.attrelid = i.indrelid AND a.attnum = ANY(i.indkey)
+            WHERE i.indrelid = to_regclass($1)
+            AND i.indisprimary
+            ORDER BY array_position(i.indkey::int2[], a.attnum);
+        "#;
+        let key: Vec<String> = sqlx::query_scalar(key_query)
+            .bind(format!("\"{}\".\"{}\"", self.target_schema, table))
+            .fetch_all(&self.target_conn)
+            .await
+            .map_err(|e| CustomError::query(Phase::Data, key_query, e).with_table(table))?;
+        if key.is_empty() {
+            return Err(CustomError::structure(
+                Phase::Data,
+                table,
+                "no primary key to match rows on, set `key` in the table settings",
+            ));
+        }
+
+        Ok(key)
+    }
+
+    // Source keys are staged in a temporary table of the target session and compared in
+    // their text form, so neither side is held in memory. A key with a NULL part can't
+    // be matched, so its row is kept.
+    async fn delete_missing(&self, table: &str, snapshot: Option<&Snapshot>) -> CustomResult<()> {
+        let key: Vec<String> = self
+            .get_key(table)
+            .await?
+            .iter()
+            .map(|name| format!("\"{}\"", name))
+            .collect();
+        if self.plan.is_dry_run() {
+            self.plan.note(
+                Phase::Data,
+                format!(
+                    "DELETE FROM {}.{} rows whose ({}) is not in the source",
+                    self.target_schema,
+                    table,
+                    key.join(", ")
+                )
+                .as_str(),
+            );
+            return Ok(());
+        }
+
+        let staged: Vec<String> = (0..key.len())
+            .map(|index| format!("key_{}", index))
+            .collect();
+        let not_null: Vec<String> = key
+            .iter()
+            .map(|column| format!("{} IS NOT NULL", column))
+            .collect();
+        let mut target = self
+            .target_conn
+            .acquire()
+            .await
+            .map_err(CustomError::connection)?;
+        let columns: Vec<String> = staged.iter().map(|name| format!("{} text", name)).collect();
+        let create_queries = [
+            format!("DROP TABLE IF EXISTS pg_temp.{}", KEYS_TABLE),
+            format!("CREATE TEMP TABLE {} ({})", KEYS_TABLE, columns.join(", ")),
+        ];
+        for query in create_queries {
+            execute(&mut *target, Phase::Data, &query)
+                .await
+                .map_err(|e| CustomError::query(Phase::Data, &query, e).with_table(table))?;
+        }
+
+        let columns: Vec<String> = key.iter().map(|name| format!("{}::text", name)).collect();
+        let select_query = format!(
+            "SELECT {} FROM {}.{} WHERE {}",
+            columns.join(", "),
+            self.source_schema,
+            table,
+            not_null.join(" AND ")
+        );
+        let batch_size = self.config.technology.batch_size.max(1);
+        let mut source = self.begin_source(snapshot).await?;
+        let mut rows = sqlx::query(&select_query).fetch(&mut *source);
+        let mut batch: Vec<String> = Vec::with_capacity(batch_size);
+        while let Some(row) = rows
+            .try_next()
+            .await
+            .map_err(|e| CustomError::query(Phase::Data, &select_query, e).with_table(table))?
+        {
+            let literals: Vec<String> = (0..row.len())
+                .map(|index| {
+                    let value = row.try_get::<Option<String>, _>(index).ok().flatten();
+                    ValueKind::Text.literal(value.as_deref())
+                })
+                .collect();
+            batch.push(format!("({})", literals.join(", ")));
+            if batch.len() >= batch_size {
+                self.stage_keys(&mut target, table, &batch).await?;
+                batch.clear();
+            }
+        }
+        if !batch.is_empty() {
+            self.stage_keys(&mut target, table, &batch).await?;
+        }
+        drop(rows);
+
+        let matches: Vec<String> = key
+            .iter()
+            .zip(&staged)
+            .map(|(column, staged)| format!("k.{} = t.{}::text", staged, column))
+            .collect();
+        let delete_query = format!(
+            "DELETE FROM {}.{} t WHERE {} AND NOT EXISTS (SELECT 1 FROM {} k WHERE {})",
+            self.target_schema,
+            table,
+            not_null.join(" AND "),
+            KEYS_TABLE,
+            matches.join(" AND ")
+        );
+        let deleted = sqlx::query(&delete_query)
+            .execute(&mut *target)
+            .await
+            .map_err(|e| {
+                self.logger.error(e.to_string().as_str());
+                CustomError::query(Phase::Data, &delete_query, e).with_table(table)
+            })?
+            .rows_affected();
+        let drop_query = format!("DROP TABLE {}", KEYS_TABLE);
+        execute(&mut *target, Phase::Data, &drop_query)
+            .await
+            .map_err(|e| CustomError::query(Phase::Data, &drop_query, e).with_table(table))?;
+
+        self.logger.info(
+            format!(
+                "Deleted {} rows of table {} missing in the source",
+                deleted, table
+            )
+            .as_str(),
+        );
+        Ok(())
+    }
+
+    async fn stage_keys(
+        &self,
+        target: &mut PgConnection,
+        table: &str,
+        batch: &[String],
+    ) -> CustomResult<()> {
+        let query = format!("INSERT INTO {} VALUES {}", KEYS_TABLE, batch.join(", "));
+        execute(target, Phase::Data, &query)
+            .await
+            .map_err(|e| CustomError::query(Phase::Data, &query, e).with_table(table))
+    }
+
+    async fn create_state_table(&self) -> CustomResult<()> {
+        let query = format!(
+            "CREATE TABLE IF NOT EXISTS {}.{} (\
+             table_name text PRIMARY KEY, \
+             watermark_column text NOT NULL, \
+             watermark text NOT NULL, \
+             synced_at timestamptz NOT NULL DEFAULT now())",
+            self.target_schema, STATE_TABLE
+        );
+        execute(&self.target_conn, Phase::Data, &query)
+            .await
+            .map_err(|e| {
+                self.logger.error(e.to_string().as_str());
+                CustomError::query(Phase::Data, &query, e)
             })
-            .collect::<Vec<(String, String, String)>>();
+    }
+
+    // A watermark stored for another column starts the table over.
+    async fn read_watermark(&self, table: &str, column: &str) -> CustomResult<Option<String>> {
+        // Only recorded in the plan on --dry-run, so the table may not exist yet
+        let exists_query = "SELECT to_regclass($1) IS NOT NULL";
+        let exists: bool = sqlx::query_scalar(exists_query)
+            .bind(format!("\"{}\".{}", self.target_schema, STATE_TABLE))
+            .fetch_one(&self.target_conn)
+            .await
+            .map_err(|e| CustomError::query(Phase::Data, exists_query, e).with_table(table))?;
+        if !exists {
+            return Ok(None);
+        }
 
-        let rows = self.get_rows(&select_query).await?;
+        let query = format!(
+            "SELECT watermark FROM {}.{} WHERE table_name = $1 AND watermark_column = $2",
+            self.target_schema, STATE_TABLE
+        );
+        sqlx::query_scalar(&query)
+            .bind(table)
+            .bind(column)
+            .fetch_optional(&self.target_conn)
+            .await
+            .map_err(|e| CustomError::query(Phase::Data, &query, e).with_table(table))
+    }
+
+    async fn store_watermark(
+        &self,
+        table: &str,
+        column: &str,
+        watermark: &str,
+    ) -> CustomResult<()> {
+        let literal = |value: &str| ValueKind::Text.literal(Some(value));
+        let query = format!(
+            "INSERT INTO {}.{} (table_name, watermark_column, watermark) VALUES ({}, {}, {}) \
+             ON CONFLICT (table_name) DO UPDATE SET watermark_column = EXCLUDED.watermark_column, \
+             watermark = EXCLUDED.watermark, synced_at = now()",
+            self.target_schema,
+            STATE_TABLE,
+            literal(table),
+            literal(column),
+            literal(watermark)
+        );
+        execute(&self.target_conn, Phase::Data, &query)
+            .await
+            .map_err(|e| {
+                self.logger.error(e.to_string().as_str());
+                CustomError::query(Phase::Data, &query, e).with_table(table)
+            })
+    }
 
-        if rows.is_empty() {
+    async fn transfer_rows(
+        &self,
+        table: &str,
+        select_query: &str,
+        columns: &[(String, String, String)],
+        transfer: TransferMode,
+        snapshot: Option<&Snapshot>,
+    ) -> CustomResult<()> {
+        match transfer {
+            TransferMode::Insert => {
+                let mut source = self.begin_source(snapshot).await?;
+                let mut target = self
+                    .target_conn
+                    .acquire()
+                    .await
+                    .map_err(CustomError::connection)?;
+
+                let insert = Insert {
+                    table,
+                    columns,
+                    on_conflict: None,
+                };
+                let mut progress = Progress::default();
+                let result = self
+                    .insert_rows(
+                        &mut source,
+                        &mut target,
+                        &insert,
+                        select_query,
+                        &mut progress,
+                    )
+                    .await;
+                // Batches are committed one by one, so rows written before a failure stay
+                self.reporter
+                    .rows(table, progress.rows, progress.rows, progress.bytes);
+                result
+            }
+            TransferMode::Copy => {
+                // COPY FROM is all-or-nothing, so a rejected binary stream leaves
+                // nothing behind and the table can be copied again as CSV.
+                if let Err(err) = self.copy_table(table, columns, "binary", snapshot).await {
+                    self.logger.warn(
+                        format!(
+                            "Binary COPY failed for table {}, retrying as CSV: {}",
+                            table, err
+                        )
+                        .as_str(),
+                    );
+                    return self.copy_table(table, columns, "csv", snapshot).await;
+                }
+                Ok(())
+            }
+            TransferMode::CopyCsv => self.copy_table(table, columns, "csv", snapshot).await,
+        }
+    }
+
+    async fn copy_table(
+        &self,
+        table: &str,
+        columns: &[(String, String, String)],
+        format: &str,
+        snapshot: Option<&Snapshot>,
+    ) -> CustomResult<()> {
+        let mut source = self.begin_source(snapshot).await?;
+        let mut target = self
+            .target_conn
+            .acquire()
+            .await
+            .map_err(CustomError::connection)?;
+
+        let progress = self
+            .copy_rows(&mut source, &mut target, table, columns, format, None)
+            .await?;
+        self.reporter
+            .rows(table, progress.rows, progress.rows, progress.bytes);
+        Ok(())
+    }
+
+    async fn insert_rows(
+        &self,
+        source: &mut PgConnection,
+        target: &mut PgConnection,
+        insert: &Insert<'_>,
+        select_query: &str,
+        progress: &mut Progress,
+    ) -> CustomResult<()> {
+        let table = insert.table;
+        let batch_size = self.config.technology.batch_size;
+
+        // Rows are streamed from the source and written every `batch_size` rows,
+        // so memory stays flat regardless of the table size.
+        let mut rows = sqlx::query(select_query).fetch(source);
+        let mut batch: Vec<PgRow> = Vec::with_capacity(batch_size);
+        let mut rows_written = 0;
+        while let Some(row) = rows.try_next().await.map_err(|e| {
+            self.logger
+                .error(format!("Failed to fetch data: {}", e).as_str());
+            CustomError::query(Phase::Data, select_query, e).with_table(table)
+        })? {
+            batch.push(row);
+            if batch.len() >= batch_size {
+                self.write_batch(target, insert, &batch, progress).await?;
+                rows_written += batch.len();
+                batch.clear();
+            }
+        }
+        if !batch.is_empty() {
+            self.write_batch(target, insert, &batch, progress).await?;
+            rows_written += batch.len();
+        }
+
+        if rows_written == 0 {
             self.logger
                 .debug(format!("No data to migrate for table: {}", table).as_str());
 
             return Ok(());
         }
 
-        // Build the INSERT statement with multiple rows
-        let column_list: Vec<String> = self.get_column_list(&columns)?;
+        self.logger
+            .debug(format!("Migrated {} rows for table: {}", rows_written, table).as_str());
+        Ok(())
+    }
+
+    async fn copy_rows(
+        &self,
+        source: &mut PgConnection,
+        target: &mut PgConnection,
+        table: &str,
+        columns: &[(String, String, String)],
+        format: &str,
+        condition: Option<&str>,
+    ) -> CustomResult<Progress> {
+        let column_list = self.get_column_list(columns)?.join(", ");
+        let copy_out = match condition {
+            Some(condition) => format!(
+                "COPY (SELECT {} FROM {}.{} WHERE {}) TO STDOUT (FORMAT {})",
+                column_list, self.source_schema, table, condition, format
+            ),
+            None => format!(
+                "COPY {}.{} ({}) TO STDOUT (FORMAT {})",
+                self.source_schema, table, column_list, format
+            ),
+        };
+        let copy_in = format!(
+            "COPY {}.{} ({}) FROM STDIN (FORMAT {})",
+            self.target_schema, table, column_list, format
+        );
+        self.logger
+            .debug(format!("Copying table {} as {}", table, format).as_str());
+
+        let mut source = source
+            .copy_out_raw(&copy_out)
+            .await
+            .map_err(|e| CustomError::query(Phase::Data, &copy_out, e).with_table(table))?;
+        let mut target = target
+            .copy_in_raw(&copy_in)
+            .await
+            .map_err(|e| CustomError::query(Phase::Data, &copy_in, e).with_table(table))?;
 
-        let values_list: Vec<String> = self.get_values_list(&rows, &columns)?;
+        let mut bytes = 0;
+        loop {
+            let chunk = match source.try_next().await {
+                Ok(Some(chunk)) => chunk,
+                Ok(None) => break,
+                Err(e) => {
+                    let _ = target.abort(e.to_string()).await;
+                    return Err(CustomError::query(Phase::Data, &copy_out, e).with_table(table));
+                }
+            };
+            bytes += chunk.len() as u64;
+
+            let sent = target.send(chunk).await.map(|_| ());
+            if let Err(e) = sent {
+                let _ = target.abort(e.to_string()).await;
+                return Err(CustomError::query(Phase::Data, &copy_in, e).with_table(table));
+            }
+        }
+
+        let rows = target
+            .finish()
+            .await
+            .map_err(|e| CustomError::query(Phase::Data, &copy_in, e).with_table(table))?;
 
         self.logger
-            .debug(format!("Executing multi-row insert for table: {}", table).as_str());
-        self.execute_insert(table, &column_list, &values_list)
+            .debug(format!("Copied {} rows for table: {}", rows, table).as_str());
+        Ok(Progress { rows, bytes })
+    }
+
+    async fn write_batch(
+        &self,
+        target: &mut PgConnection,
+        insert: &Insert<'_>,
+        rows: &[PgRow],
+        progress: &mut Progress,
+    ) -> CustomResult<()> {
+        let table = insert.table;
+        let column_list = self.get_column_list(insert.columns)?;
+        let values_list: Vec<String> = self.get_values_list(table, rows, insert.columns)?;
+
+        self.logger.debug(
+            format!(
+                "Executing multi-row insert of {} rows for table: {}",
+                rows.len(),
+                table
+            )
+            .as_str(),
+        );
+        self.execute_insert(
+            target,
+            table,
+            &column_list,
+            &values_list,
+            insert.on_conflict,
+        )
+        .await?;
+
+        let bytes: usize = values_list.iter().map(|values| values.len()).sum();
+        progress.rows += rows.len() as u64;
+        progress.bytes += bytes as u64;
+        Ok(())
+    }
+
+    async fn plan_insert(
+        &self,
+        table: &str,
+        columns: &[(String, String, String)],
+        transfer: TransferMode,
+        chunk: Option<&Chunk>,
+    ) -> CustomResult<()> {
+        let count = self
+            .count_rows(table, chunk.map(|chunk| chunk.condition.as_str()))
             .await?;
 
-        self.logger
-            .debug(format!("Migrated data for table: {}", table).as_str());
+        let command = match transfer {
+            _ if self.config.tables.settings(table).upserts() => "UPSERT INTO",
+            TransferMode::Insert => "INSERT INTO",
+            TransferMode::Copy => "COPY (binary)",
+            TransferMode::CopyCsv => "COPY (csv)",
+        };
+        self.plan.note(
+            Phase::Data,
+            format!(
+                "{} {}.{} ({}){}: {} rows",
+                command,
+                self.target_schema,
+                table,
+                self.get_column_list(columns)?.join(", "),
+                chunk
+                    .map(|chunk| format!(" {} WHERE {}", chunk, chunk.condition))
+                    .unwrap_or_default(),
+                count
+            )
+            .as_str(),
+        );
         Ok(())
     }
 
+    async fn count_rows(&self, table: &str, condition: Option<&str>) -> CustomResult<i64> {
+        let mut count_query = format!("SELECT COUNT(*) FROM {}.{}", self.source_schema, table);
+        if let Some(condition) = condition {
+            count_query = format!("{} WHERE {}", count_query, condition);
+        }
+        sqlx::query_scalar(&count_query)
+            .fetch_one(&self.source_conn)
+            .await
+            .map_err(|e| CustomError::query(Phase::Data, &count_query, e).with_table(table))
+    }
+
     fn get_values_list(
         &self,
+        table: &str,
         rows: &[PgRow],
         columns: &[(String, String, String)],
     ) -> CustomResult<Vec<String>> {
-        let values_list: Vec<String> = rows
+        let kinds: Vec<ValueKind> = columns
             .iter()
-            .enumerate()
-            .map(|(row_num, row)| {
-                let values: Vec<String> = columns
-                    .iter()
-                    .enumerate()
-                    .map(|(idx, (name, data_type, _))| {
-                        let value: Option<String> = row.try_get(name.as_str()).unwrap_or(None);
-                        match value {
-                            Some(v) => {
-                                if data_type == "integer" || data_type == "bigint" {
-                                    v
-                                } else {
-                                    format!("'{}'", v.replace("'", "''"))
-                                }
-                            }
-                            None => {
-                                // For ID column (first column), use row number + 1 if NULL
-                                if idx == 0 && (data_type == "integer" || data_type == "bigint") {
-                                    (row_num + 1).to_string()
-                                } else {
-                                    "NULL".to_string()
-                                }
-                            }
-                        }
-                    })
-                    .collect();
-                format!("({})", values.join(", "))
-            })
+            .map(|(_, data_type, _)| ValueKind::from_data_type(data_type))
             .collect();
 
+        let mut values_list = Vec::with_capacity(rows.len());
+        for row in rows {
+            let mut values = Vec::with_capacity(columns.len());
+            for ((name, _, _), kind) in columns.iter().zip(&kinds) {
+                let value: Option<String> = row.try_get(name.as_str()).map_err(|err| {
+                    CustomError::structure(
+                        Phase::Data,
+                        table,
+                        format!("failed to read column {}: {}", name, err),
+                    )
+                })?;
+                values.push(kind.literal(value.as_deref()));
+            }
+            values_list.push(format!("({})", values.join(", ")));
+        }
+
         Ok(values_list)
     }
 
@@ -153,53 +1363,61 @@ impl DataMigrator {
         Ok(column_list)
     }
 
-    async fn get_rows(&self, select_query: &str) -> CustomResult<Vec<PgRow>> {
-        // Fetch data from source
-        let rows = sqlx::query(select_query)
-            .fetch_all(&self.source_conn)
-            .await
-            .map_err(|e| {
-                self.logger
-                    .error(format!("Failed to fetch data: {}", e).as_str());
-                CustomError::QueryExecution
-            })?;
-
-        Ok(rows)
-    }
+    async fn truncate_table(
+        &self,
+        table: &str,
+        order: &LoadOrder,
+        kept: &BTreeSet<String>,
+    ) -> CustomResult<()> {
+        let cascaded = order.dependents(&BTreeSet::from([table.to_string()]));
+        let emptied: Vec<&String> = cascaded.intersection(kept).collect();
+        if !emptied.is_empty() {
+            return Err(CustomError::structure(
+                Phase::Data,
+                table,
+                format!(
+                    "truncating it would also empty {:?}, whose rows are kept by their load mode",
+                    emptied
+                ),
+            ));
+        }
 
-    async fn truncate_table(&self, table: &str) -> CustomResult<()> {
         let query = format!(
             "TRUNCATE TABLE {}.{} RESTART IDENTITY CASCADE",
             self.target_schema, table
         );
-        sqlx::query(query.as_str())
-            .execute(&self.target_conn)
+        execute(&self.target_conn, Phase::Data, &query)
             .await
             .map_err(|e| {
                 self.logger.error(e.to_string().as_str());
-                CustomError::QueryExecution
+                CustomError::query(Phase::Data, &query, e).with_table(table)
             })?;
         Ok(())
     }
     async fn execute_insert(
         &self,
+        target: &mut PgConnection,
         table: &str,
         column_list: &[String],
         values_list: &[String],
+        on_conflict: Option<&str>,
     ) -> CustomResult<()> {
-        let insert_statement = format!(
-            "INSERT INTO {}.{} ({}) VALUES {}",
+        let mut insert_statement = format!(
+            // Copies explicit ids into GENERATED ALWAYS identity columns too
+            "INSERT INTO {}.{} ({}) OVERRIDING SYSTEM VALUE VALUES {}",
             self.target_schema,
             table,
             column_list.join(", "),
             values_list.join(", ")
         );
-        sqlx::query(insert_statement.as_str())
-            .execute(&self.target_conn)
+        if let Some(on_conflict) = on_conflict {
+            insert_statement = format!("{} {}", insert_statement, on_conflict);
+        }
+        execute(target, Phase::Data, &insert_statement)
             .await
             .map_err(|e| {
                 self.logger.error(e.to_string().as_str());
-                CustomError::QueryExecution
+                CustomError::query(Phase::Data, &insert_statement, e).with_table(table)
             })?;
 
         Ok(())
@@ -236,19 +1454,25 @@ impl DataMigrator {
             .map_err(|err| {
                 self.logger
                     .error(format!("Failed to get column information: {}", err).as_str());
-                CustomError::QueryExecution
+                CustomError::query(Phase::Data, query, err).with_table(table)
             })?;
 
         if columns.is_empty() {
             self.logger
                 .error(format!("No columns found for table {}.{}", schema, table).as_str());
-            return Err(CustomError::DbTableStructure);
+            return Err(CustomError::structure(
+                Phase::Data,
+                table,
+                format!("no columns found in {}.{}", schema, table),
+            ));
         }
 
-        // Build column list with proper quoting
+        // Every column is selected in a form `ValueKind::literal` can write back
         let column_list: Vec<String> = columns
             .iter()
-            .map(|(name, _, _)| format!("\"{}\"", name))
+            .map(|(name, data_type, _)| {
+                ValueKind::from_data_type(data_type).select_expression(name)
+            })
             .collect();
 
         // Build the SELECT statement
@@ -295,7 +1519,7 @@ impl DataMigrator {
             .map_err(|err| {
                 self.logger
                     .error(format!("Failed to get column information: {}", err).as_str());
-                CustomError::QueryExecution
+                CustomError::query(Phase::Data, query, err).with_table(table)
             })
     }
 }
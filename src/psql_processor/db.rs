@@ -1,13 +1,26 @@
-use sqlx::postgres::Postgres;
-use sqlx::Pool;
+use percent_encoding::{utf8_percent_encode, AsciiSet, NON_ALPHANUMERIC};
+use sqlx::postgres::{PgConnection, PgPoolOptions, Postgres};
+use sqlx::{Connection, Executor, Pool};
+use url::form_urlencoded;
 
 use crate::config::DbConfig;
-use crate::error::{CustomError, CustomResult};
+use crate::error::{CustomError, CustomResult, Phase};
+use crate::plan::Plan;
+
+// Everything except unreserved characters (RFC 3986) is escaped in userinfo and path.
+const URL_COMPONENT: &AsciiSet = &NON_ALPHANUMERIC
+    .remove(b'-')
+    .remove(b'_')
+    .remove(b'.')
+    .remove(b'~');
 
 pub async fn get_connections_pool(db_config: &DbConfig) -> CustomResult<Pool<Postgres>> {
     let logger = crate::logger::Logger::new();
     let url = get_url(db_config);
-    let pool = Pool::<Postgres>::connect(&url).await;
+    let pool = PgPoolOptions::new()
+        .max_connections(db_config.max_connections())
+        .connect(&url)
+        .await;
 
     match pool {
         Ok(pool) => {
@@ -16,16 +29,111 @@ pub async fn get_connections_pool(db_config: &DbConfig) -> CustomResult<Pool<Pos
         }
         Err(err) => {
             logger.error(format!("Can't create connection Pool: {:#?}", err).as_str());
-            Err(CustomError::DbConnection)
+            Err(CustomError::connection(err))
         }
     }
 }
 
+/// Single connection outside any pool, for sessions that must outlive the workers.
+pub async fn get_connection(db_config: &DbConfig) -> CustomResult<PgConnection> {
+    PgConnection::connect(&get_url(db_config))
+        .await
+        .map_err(CustomError::connection)
+}
+
+/// Runs a statement against the target, or only records it in the plan on `--dry-run`.
+pub async fn execute<'c, E>(conn: E, phase: Phase, statement: &str) -> Result<(), sqlx::Error>
+where
+    E: Executor<'c, Database = Postgres>,
+{
+    let plan = Plan::new();
+    if plan.is_dry_run() {
+        plan.statement(phase, statement);
+        return Ok(());
+    }
+
+    sqlx::query(statement).execute(conn).await.map(|_| ())
+}
+
+/// `(child, parent)` table pairs for every foreign key between tables of `schema`.
+pub async fn get_references(
+    conn: &Pool<Postgres>,
+    phase: Phase,
+    schema: &str,
+) -> CustomResult<Vec<(String, String)>> {
+    let query = r#"
+        SELECT DISTINCT child.relname AS child, parent.relname AS parent
+        FROM pg_constraint con
+        JOIN pg_class child ON child.oid = con.conrelid
+        JOIN pg_namespace child_ns ON child_ns.oid = child.relnamespace
+        JOIN pg_class parent ON parent.oid = con.confrelid
+        JOIN pg_namespace parent_ns ON parent_ns.oid = parent.relnamespace
+        WHERE con.contype = 'f'
+        AND child_ns.nspname = $1
+        AND parent_ns.nspname = $1;
+    "#;
+
+    sqlx::query_as(query)
+        .bind(schema)
+        .fetch_all(conn)
+        .await
+        .map_err(|err| CustomError::query(phase, query, err))
+}
+
 fn get_url(db_config: &DbConfig) -> String {
-    let url = format!(
-        "postgresql://{}:{}@{}:{}/{}",
-        db_config.username, db_config.password, db_config.host, db_config.port, db_config.database
+    let host = if db_config.host.is_empty() {
+        "localhost"
+    } else {
+        db_config.host.as_str()
+    };
+    let port = if db_config.port.is_empty() {
+        String::new()
+    } else {
+        format!(":{}", db_config.port)
+    };
+
+    let mut url = format!(
+        "postgresql://{}:{}@{}{}/{}",
+        utf8_percent_encode(&db_config.username, URL_COMPONENT),
+        utf8_percent_encode(&db_config.password, URL_COMPONENT),
+        host,
+        port,
+        utf8_percent_encode(&db_config.database, URL_COMPONENT)
     );
 
+    let mut query = form_urlencoded::Serializer::new(String::new());
+    if let Some(sslmode) = &db_config.sslmode {
+        query.append_pair("sslmode", sslmode);
+    }
+    if let Some(ssl_root_cert) = &db_config.ssl_root_cert {
+        query.append_pair("sslrootcert", ssl_root_cert);
+    }
+    if let Some(ssl_cert) = &db_config.ssl_cert {
+        query.append_pair("sslcert", ssl_cert);
+    }
+    if let Some(ssl_key) = &db_config.ssl_key {
+        query.append_pair("sslkey", ssl_key);
+    }
+    if let Some(socket) = &db_config.socket {
+        query.append_pair("host", socket);
+    }
+    if let Some(application_name) = &db_config.application_name {
+        query.append_pair("application_name", application_name);
+    }
+    // Anything else is a server run-time parameter, e.g. `statement_timeout`.
+    for (key, value) in &db_config.params {
+        query.append_pair(format!("options[{}]", key).as_str(), value);
+    }
+
+    if db_config.read_only {
+        query.append_pair("options[default_transaction_read_only]", "on");
+    }
+
+    let query = query.finish();
+    if !query.is_empty() {
+        url.push('?');
+        url.push_str(&query);
+    }
+
     url
 }
@@ -1,21 +1,33 @@
 use crate::logger::Logger;
 use crate::psql_processor::data_migrator::DataMigrator;
 use crate::{
-    config::Config, psql_processor::structure_migrator::StructureMigrator,
-    traits::StructureMigratorTrait,
+    config::Config,
+    psql_processor::structure_migrator::StructureMigrator,
+    traits::{MigratorTrait, StructureMigratorTrait},
 };
 use std::time::Instant;
 
-use crate::error::CustomResult;
+use crate::config::StructureMode;
+use crate::diff::SchemaDiff;
+use crate::error::{CustomError, CustomResult, Phase};
+use crate::report::Reporter;
 
 use super::pg_dump_migrator::PgDumpMigrator;
+use super::schema_diff::SchemaDiffer;
+use super::sequence_sync::SequenceSync;
+use super::structure_sync::StructureSync;
+use super::verifier::Verifier;
 
 pub struct Migrator {
     pub config: Config,
 }
 
-impl Migrator {
-    pub async fn migrate(&self) -> CustomResult<()> {
+impl MigratorTrait for Migrator {
+    fn new(config: Config) -> Self {
+        Self { config }
+    }
+
+    async fn migrate(&self) -> CustomResult<()> {
         assert_ne!(
             self.config.target.schema, None,
             "Target schema is not provided"
@@ -26,11 +38,13 @@ impl Migrator {
         );
 
         let logger = Logger::new();
+        let reporter = Reporter::new();
 
+        let mut structure_failure = None;
         if self.config.technology.copy_structure {
             logger.info("Migrating structure. start");
             let structure_migration_start_time = Instant::now();
-            self.migrate_structure().await?;
+            let structure_result = self.migrate_structure().await;
             let structure_migration_end_time = Instant::now();
             let structure_migration_elapsed_time =
                 structure_migration_end_time - structure_migration_start_time;
@@ -41,6 +55,15 @@ impl Migrator {
                 )
                 .as_str(),
             );
+            reporter.run_phase(Phase::Structure, structure_migration_elapsed_time);
+
+            if let Err(err) = structure_result {
+                if self.config.run.fail_fast || !matches!(err, CustomError::TablesFailed { .. }) {
+                    return Err(err);
+                }
+                logger.warn("Continuing with data migration after structure failures");
+                structure_failure = Some(err);
+            }
         } else {
             logger.warn("Skipping structure migration");
         }
@@ -48,21 +71,76 @@ impl Migrator {
         if self.config.technology.copy_data {
             logger.info("Migrating data");
             let data_migration_start_time = Instant::now();
-            self.migrate_data().await?;
+            let data_result = self.migrate_data().await;
             let data_migration_end_time = Instant::now();
             let data_migration_elapsed_time = data_migration_end_time - data_migration_start_time;
             logger.info(format!("Migrated data in {:?}", data_migration_elapsed_time).as_str());
+            reporter.run_phase(Phase::Data, data_migration_elapsed_time);
+
+            let data_failure = match data_result {
+                Ok(()) => None,
+                Err(err @ CustomError::TablesFailed { .. }) if !self.config.run.fail_fast => {
+                    Some(err)
+                }
+                Err(err) => return Err(err),
+            };
+
+            // Rows are copied with their ids, so sequences have to be moved past them
+            logger.info("Synchronizing sequences");
+            let sequence_sync_start_time = Instant::now();
+            let sequence_result = self.sync_sequences().await;
+            let sequence_sync_elapsed_time = sequence_sync_start_time.elapsed();
+            logger.info(
+                format!("Synchronized sequences in {:?}", sequence_sync_elapsed_time).as_str(),
+            );
+            reporter.run_phase(Phase::Sequence, sequence_sync_elapsed_time);
+
+            let verify_result = match self.config.run.verify {
+                true => self.verify().await,
+                false => Ok(()),
+            };
+
+            if let Some(err) = data_failure {
+                return Err(err);
+            }
+            sequence_result?;
+            verify_result?;
         } else {
             logger.warn("Skipping data migration");
         }
 
-        Ok(())
+        structure_failure.map_or(Ok(()), Err)
     }
 
+    async fn verify(&self) -> CustomResult<()> {
+        let logger = Logger::new();
+        logger.info("Verifying tables");
+        let verify_start_time = Instant::now();
+        let verify_result = match Verifier::new(self.config.clone()).await {
+            Ok(verifier) => verifier.verify().await,
+            Err(err) => Err(err),
+        };
+        let verify_elapsed_time = verify_start_time.elapsed();
+        logger.info(format!("Verified tables in {:?}", verify_elapsed_time).as_str());
+        Reporter::new().run_phase(Phase::Verify, verify_elapsed_time);
+
+        verify_result
+    }
+
+    async fn diff(&self) -> CustomResult<SchemaDiff> {
+        let differ = SchemaDiffer::new(self.config.clone()).await?;
+        differ.diff().await
+    }
+}
+
+impl Migrator {
     async fn migrate_structure(&self) -> CustomResult<()> {
         if self.config.technology.use_pg_dump {
             let pg_dump_migrator = PgDumpMigrator::new(self.config.clone()).await?;
             pg_dump_migrator.migrate_structure().await?;
+        } else if self.config.technology.structure_mode == StructureMode::Sync {
+            let structure_sync = StructureSync::new(self.config.clone()).await?;
+            structure_sync.migrate().await?;
         } else {
             let struct_migrator = StructureMigrator::new(self.config.clone()).await?;
             struct_migrator.migrate().await?;
@@ -71,6 +149,11 @@ impl Migrator {
         Ok(())
     }
 
+    async fn sync_sequences(&self) -> CustomResult<()> {
+        let sequence_sync = SequenceSync::new(self.config.clone()).await?;
+        sequence_sync.migrate().await
+    }
+
     async fn migrate_data(&self) -> CustomResult<()> {
         if self.config.technology.use_pg_dump {
             let pg_dump_migrator = PgDumpMigrator::new(self.config.clone()).await?;
@@ -2,5 +2,11 @@ pub mod data_migrator;
 pub mod db;
 pub mod migrator;
 pub mod pg_dump_migrator;
+pub mod schema_diff;
+pub mod sequence_sync;
+pub mod snapshot;
 pub mod structure_migrator;
+pub mod structure_sync;
 pub mod table_migrator;
+pub mod value_mapper;
+pub mod verifier;
@@ -4,16 +4,22 @@ use sqlx::{Pool, Postgres};
 
 use crate::{
     config::Config,
-    error::{CustomError, CustomResult},
+    error::{CustomError, CustomResult, Phase},
     logger::Logger,
-    psql_processor::db::get_connections_pool,
+    plan::Plan,
+    psql_processor::db::{execute, get_connections_pool},
+    report::{Reporter, SkipReason},
 };
 
+// Passwords are handed to the shell through the environment so they never
+// appear in the command line or in the logged command on failure.
+const SOURCE_PASSWORD_ENV: &str = "DB_COPY_SOURCE_PASSWORD";
+const TARGET_PASSWORD_ENV: &str = "DB_COPY_TARGET_PASSWORD";
+
 pub struct PgDumpMigrator {
     pub config: Config,
     pub target_schema: String,
     pub source_schema: String,
-    pub source_conn: Pool<Postgres>,
     pub target_conn: Pool<Postgres>,
     pub logger: Logger,
 }
@@ -24,10 +30,6 @@ impl PgDumpMigrator {
         assert_ne!(config.source.schema, None, "Source schema is not provided");
 
         let logger = Logger::new();
-        logger.info("Connecting to source database");
-        let source_conn = get_connections_pool(&config.source).await?;
-        logger.info("Connected to source database");
-
         logger.info("Connecting to target database");
         let target_conn = get_connections_pool(&config.target).await?;
         logger.info("Connected to target database");
@@ -36,7 +38,6 @@ impl PgDumpMigrator {
             config: config.clone(),
             target_schema: config.target.schema.as_ref().unwrap().clone(),
             source_schema: config.source.schema.as_ref().unwrap().clone(),
-            source_conn,
             target_conn,
             logger,
         })
@@ -51,8 +52,8 @@ impl PgDumpMigrator {
             .as_str(),
         );
         let mut command = format!(
-            "PGPASSWORD='{0}' pg_dump -U {1} -h {2} -d {3} --schema={4} --data-only",
-            self.config.source.password,
+            "PGPASSWORD=\"${0}\" pg_dump -U {1} -h {2} -d {3} --schema={4} --data-only",
+            SOURCE_PASSWORD_ENV,
             self.config.source.username,
             self.config.source.host,
             self.config.source.database,
@@ -65,8 +66,8 @@ impl PgDumpMigrator {
 
         command.push_str(
             format!(
-                " | PGPASSWORD='{0}' psql -U {1} -d {2} -h {3}",
-                self.config.target.password,
+                " | PGPASSWORD=\"${0}\" psql -U {1} -d {2} -h {3}",
+                TARGET_PASSWORD_ENV,
                 self.config.target.username,
                 self.config.target.database,
                 self.config.target.host
@@ -74,14 +75,26 @@ impl PgDumpMigrator {
             .as_str(),
         );
 
+        let plan = Plan::new();
+        if plan.is_dry_run() {
+            plan.note(Phase::Data, &command);
+            return Ok(());
+        }
+
         let output = Command::new("zsh")
             .arg("-c")
             .arg(&command)
+            .env(SOURCE_PASSWORD_ENV, &self.config.source.password)
+            .env(TARGET_PASSWORD_ENV, &self.config.target.password)
             .output()
             .map_err(|err| {
                 self.logger
                     .error(format!("Failed to execute pg_dump command: {}", err).as_str());
-                CustomError::CommandExecution
+                CustomError::CommandExecution {
+                    command: command.clone(),
+                    message: "failed to spawn shell".to_string(),
+                    source: Some(err),
+                }
             })?;
 
         if !output.status.success() {
@@ -90,14 +103,22 @@ impl PgDumpMigrator {
             self.logger
                 .error(format!("Error: {}", String::from_utf8_lossy(&output.stderr)).as_str());
 
-            return Err(CustomError::CommandExecution);
+            return Err(CustomError::CommandExecution {
+                command,
+                message: format!("exited with {}", output.status),
+                source: None,
+            });
         }
 
         if !output.stderr.is_empty() {
             self.logger
                 .error(format!("Error: {}", String::from_utf8_lossy(&output.stderr)).as_str());
 
-            return Err(CustomError::CommandExecution);
+            return Err(CustomError::CommandExecution {
+                command,
+                message: String::from_utf8_lossy(&output.stderr).trim().to_string(),
+                source: None,
+            });
         }
 
         self.logger.info(
@@ -118,8 +139,8 @@ impl PgDumpMigrator {
             .info(format!("Re-created target schema {}", self.target_schema).as_str());
 
         let mut command = format!(
-            "PGPASSWORD='{0}' pg_dump -U {1} -h {2} -d {3} --schema={4} --schema-only",
-            self.config.source.password,
+            "PGPASSWORD=\"${0}\" pg_dump -U {1} -h {2} -d {3} --schema={4} --schema-only",
+            SOURCE_PASSWORD_ENV,
             self.config.source.username,
             self.config.source.host,
             self.config.source.database,
@@ -128,12 +149,13 @@ impl PgDumpMigrator {
 
         for table in &self.config.tables.skip {
             command.push_str(format!(" --exclude-table={}", table).as_str());
+            Reporter::new().skipped(table, SkipReason::Config);
         }
 
         command.push_str(
             format!(
-                " | PGPASSWORD='{0}' psql -U {1} -d {2} -h {3}",
-                self.config.target.password,
+                " | PGPASSWORD=\"${0}\" psql -U {1} -d {2} -h {3}",
+                TARGET_PASSWORD_ENV,
                 self.config.target.username,
                 self.config.target.database,
                 self.config.target.host
@@ -141,14 +163,26 @@ impl PgDumpMigrator {
             .as_str(),
         );
 
+        let plan = Plan::new();
+        if plan.is_dry_run() {
+            plan.note(Phase::Structure, &command);
+            return Ok(());
+        }
+
         let output = Command::new("zsh")
             .arg("-c")
             .arg(&command)
+            .env(SOURCE_PASSWORD_ENV, &self.config.source.password)
+            .env(TARGET_PASSWORD_ENV, &self.config.target.password)
             .output()
             .map_err(|err| {
                 self.logger
                     .error(format!("Failed to execute pg_dump command: {}", err).as_str());
-                CustomError::CommandExecution
+                CustomError::CommandExecution {
+                    command: command.clone(),
+                    message: "failed to spawn shell".to_string(),
+                    source: Some(err),
+                }
             })?;
 
         if !output.status.success() {
@@ -157,7 +191,11 @@ impl PgDumpMigrator {
             self.logger
                 .error(format!("Error: {}", String::from_utf8_lossy(&output.stderr)).as_str());
 
-            return Err(CustomError::CommandExecution);
+            return Err(CustomError::CommandExecution {
+                command,
+                message: format!("exited with {}", output.status),
+                source: None,
+            });
         }
 
         if !output.stderr.is_empty() {
@@ -170,23 +208,21 @@ impl PgDumpMigrator {
 
     async fn recreate_schema(&self) -> CustomResult<()> {
         let drop_schema_query = format!("DROP SCHEMA IF EXISTS {} CASCADE;", self.target_schema);
-        sqlx::query(&drop_schema_query)
-            .execute(&self.target_conn)
+        execute(&self.target_conn, Phase::Schema, &drop_schema_query)
             .await
             .map_err(|err| {
                 self.logger
                     .error(format!("Failed to drop schema: {}", err).as_str());
-                CustomError::QueryExecution
+                CustomError::query(Phase::Schema, &drop_schema_query, err)
             })?;
 
         let create_schema_query = format!("CREATE SCHEMA IF NOT EXISTS {};", self.target_schema);
-        sqlx::query(&create_schema_query)
-            .execute(&self.target_conn)
+        execute(&self.target_conn, Phase::Schema, &create_schema_query)
             .await
             .map_err(|err| {
                 self.logger
                     .error(format!("Failed to create schema: {}", err).as_str());
-                CustomError::QueryExecution
+                CustomError::query(Phase::Schema, &create_schema_query, err)
             })?;
 
         self.logger
@@ -0,0 +1,120 @@
+use sqlx::{Pool, Postgres};
+
+use crate::config::Config;
+use crate::diff::{compare, strip_schema, Schema, SchemaDiff, TableSchema};
+use crate::error::CustomResult;
+use crate::logger::Logger;
+use crate::traits::StructureMigratorTrait;
+
+use super::structure_migrator::StructureMigrator;
+use super::table_migrator::TableMigrator;
+
+/// Reads the catalog of source and target with the queries the structure copy uses
+/// and compares them.
+pub struct SchemaDiffer {
+    pub structure_migrator: StructureMigrator,
+    pub table_migrator: TableMigrator,
+    pub logger: Logger,
+}
+
+impl SchemaDiffer {
+    pub async fn new(config: Config) -> CustomResult<Self> {
+        let table_migrator = TableMigrator::new(&config).await?;
+        let structure_migrator = StructureMigrator::new(config).await?;
+
+        Ok(Self {
+            structure_migrator,
+            table_migrator,
+            logger: Logger::new(),
+        })
+    }
+
+    pub async fn diff(&self) -> CustomResult<SchemaDiff> {
+        let migrator = &self.structure_migrator;
+        self.logger.info("Reading source schema");
+        let source = self
+            .read_schema(&migrator.source_conn, &migrator.source_schema)
+            .await?;
+        self.logger.info("Reading target schema");
+        let target = self
+            .read_schema(&migrator.target_conn, &migrator.target_schema)
+            .await?;
+
+        Ok(compare(&source, &target))
+    }
+
+    async fn read_schema(&self, conn: &Pool<Postgres>, schema: &str) -> CustomResult<Schema> {
+        let migrator = &self.structure_migrator;
+        let table_migrator = &self.table_migrator;
+        let mut result = Schema::default();
+
+        for enum_info in migrator.list_all_enums(conn).await? {
+            if enum_info.schema == schema {
+                result
+                    .enums
+                    .insert(enum_info.enum_name, enum_info.enum_values.join(", "));
+            }
+        }
+
+        for table in migrator.list_all_tables(conn).await? {
+            if table.schema != schema || migrator.skip_reason(&table.table_name).is_some() {
+                continue;
+            }
+            let name = table.table_name;
+            let mut table_schema = TableSchema::default();
+
+            for column in table_migrator
+                .get_column_definitions(conn, schema, &name)
+                .await?
+            {
+                table_schema
+                    .columns
+                    .insert(column.column_name, strip_schema(&column.definition, schema));
+            }
+            for index_ddl in table_migrator.get_index_ddl(conn, schema, &name).await? {
+                table_schema
+                    .indexes
+                    .insert(index_name(&index_ddl), strip_schema(&index_ddl, schema));
+            }
+            for constraint_ddl in table_migrator
+                .get_constraint_ddl(conn, schema, &name)
+                .await?
+            {
+                table_schema.constraints.insert(
+                    table_migrator.extract_constraint_name(&constraint_ddl),
+                    strip_schema(&constraint_ddl, schema),
+                );
+            }
+
+            for sequence in table_migrator
+                .get_table_sequences(conn, schema, &name)
+                .await?
+            {
+                let (_, sequence) = table_migrator.extract_sequence_parts(&sequence, schema);
+                let sequence = table_migrator.clean_sequence_name(sequence);
+                // A default pointing at a sequence that doesn't exist shows up in
+                // the column definitions already
+                if let Some(definition) = table_migrator
+                    .get_sequence_definition(conn, schema, &sequence)
+                    .await?
+                {
+                    result
+                        .sequences
+                        .insert(sequence, strip_schema(&definition, schema));
+                }
+            }
+
+            result.tables.insert(name, table_schema);
+        }
+
+        Ok(result)
+    }
+}
+
+/// Name of a `CREATE [UNIQUE] INDEX name ON ...` statement from `pg_indexes.indexdef`.
+pub fn index_name(ddl: &str) -> String {
+    ddl.split_once("INDEX ")
+        .and_then(|(_, rest)| rest.split_once(" ON "))
+        .map(|(name, _)| name.trim().to_string())
+        .unwrap_or_else(|| ddl.to_string())
+}
@@ -0,0 +1,196 @@
+use sqlx::{FromRow, Pool, Postgres};
+
+use crate::checkpoint::Checkpoint;
+use crate::config::Config;
+use crate::error::{CustomError, CustomResult, Phase};
+use crate::logger::Logger;
+use crate::report::Reporter;
+
+use super::db::{execute, get_connections_pool};
+
+#[derive(Debug, FromRow)]
+struct TableSequence {
+    sequence_name: String,
+    column_name: String,
+}
+
+/// Post-data phase: moves every sequence behind a copied table past the copied ids.
+pub struct SequenceSync {
+    pub config: Config,
+    pub target_schema: String,
+    pub source_schema: String,
+    pub source_conn: Pool<Postgres>,
+    pub target_conn: Pool<Postgres>,
+    pub logger: Logger,
+    pub reporter: Reporter,
+}
+
+impl SequenceSync {
+    pub async fn new(config: Config) -> CustomResult<Self> {
+        let logger = Logger::new();
+        let source_conn = get_connections_pool(&config.source).await?;
+        let target_conn = get_connections_pool(&config.target).await?;
+
+        Ok(Self {
+            config: config.clone(),
+            target_schema: config.target.schema.clone().unwrap(),
+            source_schema: config.source.schema.clone().unwrap(),
+            source_conn,
+            target_conn,
+            logger,
+            reporter: Reporter::new(),
+        })
+    }
+
+    pub async fn migrate(&self) -> CustomResult<()> {
+        let checkpoint = Checkpoint::new();
+        let mut failed_tables = Vec::new();
+        let mut attempted = 0;
+        for table in &self.config.tables.data_source {
+            if checkpoint.is_done(table, Phase::Sequence) {
+                continue;
+            }
+            attempted += 1;
+            if let Err(err) = self.sync_table(table).await {
+                self.reporter.failure(table, Phase::Sequence, &err);
+                self.logger.error(
+                    format!("Failed to sync sequences for table {}: {}", table, err).as_str(),
+                );
+                failed_tables.push(table.to_string());
+                if self.config.run.fail_fast {
+                    self.logger
+                        .error("Stopping after first failure (--fail-fast)");
+                    break;
+                }
+            } else {
+                checkpoint.complete(table, Phase::Sequence);
+            }
+        }
+
+        if !failed_tables.is_empty() {
+            return Err(CustomError::TablesFailed {
+                phase: Phase::Sequence,
+                failed: failed_tables,
+                total: attempted,
+            });
+        }
+        Ok(())
+    }
+
+    async fn sync_table(&self, table: &str) -> CustomResult<()> {
+        let sequences = self.get_table_sequences(table).await?;
+
+        for sequence in sequences {
+            let source_value = self.get_source_value(table, &sequence).await?;
+            let target_max = self
+                .get_max(&self.target_conn, &self.target_schema, table, &sequence)
+                .await?;
+
+            // The target may already hold ids past the source sequence (append loads)
+            let Some(value) = source_value.max(target_max) else {
+                self.logger.debug(
+                    format!(
+                        "Sequence {}.{} was never used, leaving it as is",
+                        self.target_schema, sequence.sequence_name
+                    )
+                    .as_str(),
+                );
+                continue;
+            };
+
+            let setval_query = format!(
+                "SELECT setval('\"{}\".\"{}\"', {}, true)",
+                self.target_schema, sequence.sequence_name, value
+            );
+            execute(&self.target_conn, Phase::Sequence, &setval_query)
+                .await
+                .map_err(|err| {
+                    CustomError::query(Phase::Sequence, &setval_query, err).with_table(table)
+                })?;
+
+            self.logger.debug(
+                format!(
+                    "Set sequence {}.{} to {}",
+                    self.target_schema, sequence.sequence_name, value
+                )
+                .as_str(),
+            );
+        }
+
+        Ok(())
+    }
+
+    async fn get_table_sequences(&self, table: &str) -> CustomResult<Vec<TableSequence>> {
+        // Sequences owned by a column (serial, identity) or used by a column default
+        let query = r#"
+            SELECT s.relname AS sequence_name, a.attname AS column_name
+            FROM pg_depend d
+            JOIN pg_class s ON s.oid = d.objid AND s.relkind = 'S'
+            JOIN pg_attribute a ON a.attrelid = d.refobjid AND a.attnum = d.refobjsubid
+            WHERE d.classid = 'pg_class'::regclass
+            AND d.refclassid = 'pg_class'::regclass
+            AND d.deptype IN ('a', 'i')
+            AND d.refobjid = to_regclass($1)
+            UNION
+            SELECT s.relname AS sequence_name, a.attname AS column_name
+            FROM pg_attrdef ad
+            JOIN pg_attribute a ON a.attrelid = ad.adrelid AND a.attnum = ad.adnum
+            JOIN pg_depend d ON d.classid = 'pg_attrdef'::regclass AND d.objid = ad.oid
+            JOIN pg_class s ON s.oid = d.refobjid AND s.relkind = 'S'
+            WHERE ad.adrelid = to_regclass($1);
+        "#;
+
+        sqlx::query_as(query)
+            .bind(format!("\"{}\".\"{}\"", self.target_schema, table))
+            .fetch_all(&self.target_conn)
+            .await
+            .map_err(|err| CustomError::query(Phase::Sequence, query, err).with_table(table))
+    }
+
+    // `last_value` of the same sequence on the source, or MAX of the column if it was
+    // never called there (or doesn't exist under that name).
+    async fn get_source_value(
+        &self,
+        table: &str,
+        sequence: &TableSequence,
+    ) -> CustomResult<Option<i64>> {
+        let query = r#"
+            SELECT last_value
+            FROM pg_sequences
+            WHERE schemaname = $1 AND sequencename = $2;
+        "#;
+
+        let last_value: Option<Option<i64>> = sqlx::query_scalar(query)
+            .bind(&self.source_schema)
+            .bind(&sequence.sequence_name)
+            .fetch_optional(&self.source_conn)
+            .await
+            .map_err(|err| CustomError::query(Phase::Sequence, query, err).with_table(table))?;
+
+        match last_value.flatten() {
+            Some(value) => Ok(Some(value)),
+            None => {
+                self.get_max(&self.source_conn, &self.source_schema, table, sequence)
+                    .await
+            }
+        }
+    }
+
+    async fn get_max(
+        &self,
+        conn: &Pool<Postgres>,
+        schema: &str,
+        table: &str,
+        sequence: &TableSequence,
+    ) -> CustomResult<Option<i64>> {
+        let query = format!(
+            "SELECT MAX(\"{}\")::bigint FROM \"{}\".\"{}\"",
+            sequence.column_name, schema, table
+        );
+
+        sqlx::query_scalar(&query)
+            .fetch_one(conn)
+            .await
+            .map_err(|err| CustomError::query(Phase::Sequence, &query, err).with_table(table))
+    }
+}
@@ -0,0 +1,54 @@
+use sqlx::postgres::PgConnection;
+use sqlx::{Pool, Postgres, Transaction};
+
+use crate::config::DbConfig;
+use crate::error::{CustomError, CustomResult, Phase};
+
+use super::db::get_connection;
+
+/// Source transaction kept open so other sessions can read the database as of the
+/// moment it was exported. The snapshot is released when this is dropped.
+pub struct Snapshot {
+    _conn: PgConnection,
+    pub id: String,
+}
+
+impl Snapshot {
+    pub async fn export(db_config: &DbConfig) -> CustomResult<Self> {
+        // Its own connection, so holding the snapshot never takes a worker's pool slot
+        let mut conn = get_connection(db_config).await?;
+
+        let begin_query = "BEGIN ISOLATION LEVEL REPEATABLE READ, READ ONLY";
+        sqlx::query(begin_query)
+            .execute(&mut conn)
+            .await
+            .map_err(|err| CustomError::query(Phase::Data, begin_query, err))?;
+
+        let export_query = "SELECT pg_export_snapshot()";
+        let id: String = sqlx::query_scalar(export_query)
+            .fetch_one(&mut conn)
+            .await
+            .map_err(|err| CustomError::query(Phase::Data, export_query, err))?;
+
+        Ok(Self { _conn: conn, id })
+    }
+
+    /// Transaction on `pool` that reads the exported snapshot.
+    pub async fn begin(
+        &self,
+        pool: &Pool<Postgres>,
+    ) -> CustomResult<Transaction<'static, Postgres>> {
+        let mut transaction = pool.begin().await.map_err(CustomError::connection)?;
+
+        let isolation_query = "SET TRANSACTION ISOLATION LEVEL REPEATABLE READ, READ ONLY";
+        let snapshot_query = format!("SET TRANSACTION SNAPSHOT '{}'", self.id);
+        for query in [isolation_query, snapshot_query.as_str()] {
+            sqlx::query(query)
+                .execute(&mut *transaction)
+                .await
+                .map_err(|err| CustomError::query(Phase::Data, query, err))?;
+        }
+
+        Ok(transaction)
+    }
+}
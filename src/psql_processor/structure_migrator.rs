@@ -1,26 +1,31 @@
 use regex::Regex;
 use sqlx::{FromRow, Pool, Postgres};
 
+use crate::checkpoint::Checkpoint;
 use crate::config::Config;
-use crate::error::{CustomError, CustomResult};
-use crate::psql_processor::db::get_connections_pool;
+use crate::dependency::sort_tables;
+use crate::error::{CustomError, CustomResult, Phase};
+use crate::parallel::run_async;
+use crate::psql_processor::db::{execute, get_connections_pool, get_references};
 use crate::traits::StructureMigratorTrait;
+use crate::watermark::STATE_TABLE;
 
 use crate::logger::Logger;
+use crate::report::Reporter;
 
 use super::table_migrator::TableMigrator;
 
 #[derive(Debug, FromRow)]
-struct EnumInfo {
-    schema: String,
-    enum_name: String,
-    enum_values: Vec<String>,
+pub struct EnumInfo {
+    pub schema: String,
+    pub enum_name: String,
+    pub enum_values: Vec<String>,
 }
 
 #[derive(Debug, Clone, FromRow)]
-struct TableInfo {
-    schema: String,
-    table_name: String,
+pub struct TableInfo {
+    pub schema: String,
+    pub table_name: String,
 }
 
 pub struct StructureMigrator {
@@ -30,6 +35,7 @@ pub struct StructureMigrator {
     pub source_conn: Pool<Postgres>,
     pub target_conn: Pool<Postgres>,
     pub logger: Logger,
+    pub reporter: Reporter,
 }
 
 impl StructureMigrator {
@@ -53,12 +59,13 @@ impl StructureMigrator {
             source_conn,
             target_conn,
             logger,
+            reporter: Reporter::new(),
         })
     }
 }
 
 impl StructureMigrator {
-    async fn list_all_enums(&self) -> CustomResult<Vec<EnumInfo>> {
+    pub async fn list_all_enums(&self, conn: &Pool<Postgres>) -> CustomResult<Vec<EnumInfo>> {
         let query = r#"
             SELECT 
                 n.nspname as schema,
@@ -72,38 +79,33 @@ impl StructureMigrator {
             ORDER BY n.nspname, t.typname;
         "#;
 
-        let enums: Vec<EnumInfo> = sqlx::query_as(query)
-            .fetch_all(&self.source_conn)
-            .await
-            .map_err(|err| {
-                self.logger
-                    .error(format!("Failed to fetch enum types: {}", err).as_str());
-                self.logger.error(query);
-                CustomError::QueryExecution
-            })?;
+        let enums: Vec<EnumInfo> = sqlx::query_as(query).fetch_all(conn).await.map_err(|err| {
+            self.logger
+                .error(format!("Failed to fetch enum types: {}", err).as_str());
+            self.logger.error(query);
+            CustomError::query(Phase::Enum, query, err)
+        })?;
 
         Ok(enums)
     }
 
     async fn recreate_schema(&self) -> CustomResult<()> {
         let drop_schema_query = format!("DROP SCHEMA IF EXISTS {} CASCADE;", self.target_schema);
-        sqlx::query(&drop_schema_query)
-            .execute(&self.target_conn)
+        execute(&self.target_conn, Phase::Schema, &drop_schema_query)
             .await
             .map_err(|err| {
                 self.logger
                     .error(format!("Failed to drop schema: {}", err).as_str());
-                CustomError::QueryExecution
+                CustomError::query(Phase::Schema, &drop_schema_query, err)
             })?;
 
         let create_schema_query = format!("CREATE SCHEMA IF NOT EXISTS {};", self.target_schema);
-        sqlx::query(&create_schema_query)
-            .execute(&self.target_conn)
+        execute(&self.target_conn, Phase::Schema, &create_schema_query)
             .await
             .map_err(|err| {
                 self.logger
                     .error(format!("Failed to create schema: {}", err).as_str());
-                CustomError::QueryExecution
+                CustomError::query(Phase::Schema, &create_schema_query, err)
             })?;
 
         self.logger
@@ -111,7 +113,7 @@ impl StructureMigrator {
         Ok(())
     }
 
-    async fn create_enum(&self, enum_info: &EnumInfo) -> CustomResult<()> {
+    pub async fn create_enum(&self, enum_info: &EnumInfo) -> CustomResult<()> {
         // Create enum in the target schema
         let values_str = enum_info
             .enum_values
@@ -136,14 +138,13 @@ impl StructureMigrator {
             self.target_schema, enum_info.enum_name, values_str
         );
 
-        sqlx::query(&create_enum_query)
-            .execute(&self.target_conn)
+        execute(&self.target_conn, Phase::Enum, &create_enum_query)
             .await
             .map_err(|err| {
                 self.logger
                     .error(format!("Failed to create enum: {}", err).as_str());
                 self.logger.error(create_enum_query.as_str());
-                CustomError::QueryExecution
+                CustomError::query(Phase::Enum, &create_enum_query, err)
             })?;
 
         self.logger.debug(
@@ -156,7 +157,7 @@ impl StructureMigrator {
         Ok(())
     }
 
-    async fn list_all_tables(&self) -> CustomResult<Vec<TableInfo>> {
+    pub async fn list_all_tables(&self, conn: &Pool<Postgres>) -> CustomResult<Vec<TableInfo>> {
         let query = r#"
             SELECT 
                 n.nspname as schema,
@@ -174,14 +175,12 @@ impl StructureMigrator {
             ORDER BY n.nspname, c.relname;
         "#;
 
-        let tables: Vec<TableInfo> = sqlx::query_as(query)
-            .fetch_all(&self.source_conn)
-            .await
-            .map_err(|err| {
+        let tables: Vec<TableInfo> =
+            sqlx::query_as(query).fetch_all(conn).await.map_err(|err| {
                 self.logger
                     .error(format!("Failed to fetch tables: {}", err).as_str());
                 self.logger.error(query);
-                CustomError::QueryExecution
+                CustomError::query(Phase::Structure, query, err)
             })?;
 
         Ok(tables)
@@ -190,15 +189,20 @@ impl StructureMigrator {
 
 impl StructureMigratorTrait for StructureMigrator {
     async fn migrate(&self) -> CustomResult<()> {
-        self.logger.info("Re-creating target schema");
-        self.recreate_schema().await?;
-        self.logger.info("Re-created target schema");
+        if Checkpoint::new().is_resuming() {
+            // Tables finished by the earlier run are kept, see `TableMigrator::migrate`
+            self.logger.info("Resuming, keeping target schema");
+        } else {
+            self.logger.info("Re-creating target schema");
+            self.recreate_schema().await?;
+            self.logger.info("Re-created target schema");
+        }
 
         self.logger.info("Migrating structure");
 
         // First migrate all enums
         self.logger.debug("Migrating enums");
-        let enums = self.list_all_enums().await?;
+        let enums = self.list_all_enums(&self.source_conn).await?;
         self.logger
             .debug(format!("Found {} enums", enums.len()).as_str());
 
@@ -212,48 +216,72 @@ impl StructureMigratorTrait for StructureMigrator {
 
         self.logger.debug("Getting all tables");
         // List all tables
-        let tables = self.list_all_tables().await?;
+        let tables = self.list_all_tables(&self.source_conn).await?;
         self.logger
             .debug(format!("Found {} tables to clone:", tables.len()).as_str());
 
-        let mut success = vec![];
-        let mut failures = vec![];
+        let mut tables_to_clone = vec![];
         let mut skipped = vec![];
-
-        let table_migrator = TableMigrator::new(&self.config).await?;
-        // Clone each table
         for table in tables {
             if table.schema != self.source_schema {
                 continue;
             }
 
-            // if !["cb_batch_runs"].contains(&table.table_name.as_str()) {
-            //     // self.logger
-            //     // .debug(format!("Skipping table {}", table.table_name).as_str());
-            //     skipped.push(table.clone());
-            //     continue;
-            // }
-
-            if self.skip_table(&table.table_name) {
+            if let Some(reason) = self.skip_reason(&table.table_name) {
                 self.logger
-                    .debug(format!("Skipping table {}", table.table_name).as_str());
+                    .debug(format!("Skipping table {} ({:?})", table.table_name, reason).as_str());
+                self.reporter.skipped(&table.table_name, reason);
                 skipped.push(table.clone());
                 continue;
             }
 
-            let res = table_migrator
-                .migrate(&table.schema, &table.table_name)
-                .await;
+            tables_to_clone.push(table.table_name);
+        }
+
+        // Referenced tables are cloned first so foreign keys find their target
+        let references =
+            get_references(&self.source_conn, Phase::Structure, &self.source_schema).await?;
+        let order = sort_tables(&tables_to_clone, &references);
 
-            match res {
-                Ok(_) => success.push(table),
-                Err(e) => {
-                    failures.push(table.clone());
-                    self.logger.error(
-                        format!("Failed to clone table {}: {}", table.table_name, e).as_str(),
-                    );
-                }
+        let mut success = vec![];
+        let mut failures = vec![];
+
+        let table_migrator = TableMigrator::new(&self.config).await?;
+        let workers = self.config.workers();
+        for level in &order.levels {
+            if self.config.run.fail_fast && !failures.is_empty() {
+                self.logger
+                    .error("Stopping after first failure (--fail-fast)");
+                break;
             }
+
+            run_async(
+                level,
+                workers,
+                |table| table_migrator.migrate(&self.source_schema, table),
+                |table, result| {
+                    match result {
+                        Ok(_) => success.push(table.to_string()),
+                        Err(e) => {
+                            failures.push(table.to_string());
+                            self.reporter.failure(table, Phase::Structure, &e);
+                            self.logger
+                                .error(format!("Failed to clone table {}: {}", table, e).as_str());
+                        }
+                    }
+                    self.logger.info(
+                        format!(
+                            "Finished table {} ({}/{})",
+                            table,
+                            success.len() + failures.len(),
+                            tables_to_clone.len()
+                        )
+                        .as_str(),
+                    );
+                    !self.config.run.fail_fast || failures.is_empty()
+                },
+            )
+            .await;
         }
 
         self.logger
@@ -266,22 +294,24 @@ impl StructureMigratorTrait for StructureMigrator {
         if !failures.is_empty() {
             self.logger
                 .error(format!("Failed to clone {} tables", failures.len()).as_str());
-            for table in failures {
+            for table in &failures {
                 self.logger.error(
-                    format!(
-                        "Failed to clone table: {}.{}",
-                        table.schema, table.table_name
-                    )
-                    .as_str(),
+                    format!("Failed to clone table: {}.{}", self.source_schema, table).as_str(),
                 );
             }
+
+            return Err(CustomError::TablesFailed {
+                phase: Phase::Structure,
+                total: success.len() + failures.len(),
+                failed: failures,
+            });
         }
 
         Ok(())
     }
 
     fn is_private_table(&self, table_name: &str) -> bool {
-        let internal_tables = [];
+        let internal_tables = [STATE_TABLE];
 
         internal_tables.contains(&table_name)
     }
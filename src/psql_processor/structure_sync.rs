@@ -0,0 +1,434 @@
+use std::collections::{BTreeMap, BTreeSet};
+
+use crate::config::Config;
+use crate::dependency::sort_tables;
+use crate::diff::{strip_schema, Change, TableDiff};
+use crate::error::{CustomError, CustomResult, Phase};
+use crate::logger::Logger;
+use crate::report::Reporter;
+
+use super::db::{execute, get_references};
+use super::schema_diff::{index_name, SchemaDiffer};
+use super::structure_migrator::EnumInfo;
+use super::table_migrator::ColumnDefinition;
+
+/// Brings the target schema in line with the source by running only the statements
+/// the schema diff asks for (`structure_mode = "sync"`). Objects that exist only in
+/// the target are kept, except for columns.
+pub struct StructureSync {
+    pub config: Config,
+    pub differ: SchemaDiffer,
+    pub logger: Logger,
+    pub reporter: Reporter,
+}
+
+impl StructureSync {
+    pub async fn new(config: Config) -> CustomResult<Self> {
+        Ok(Self {
+            differ: SchemaDiffer::new(config.clone()).await?,
+            config,
+            logger: Logger::new(),
+            reporter: Reporter::new(),
+        })
+    }
+
+    fn source_schema(&self) -> &str {
+        &self.differ.structure_migrator.source_schema
+    }
+
+    fn target_schema(&self) -> &str {
+        &self.differ.structure_migrator.target_schema
+    }
+
+    pub async fn migrate(&self) -> CustomResult<()> {
+        let create_schema_query = format!("CREATE SCHEMA IF NOT EXISTS {};", self.target_schema());
+        execute(
+            &self.differ.structure_migrator.target_conn,
+            Phase::Schema,
+            &create_schema_query,
+        )
+        .await
+        .map_err(|err| CustomError::query(Phase::Schema, &create_schema_query, err))?;
+
+        self.logger.info("Comparing source and target schema");
+        let diff = self.differ.diff().await?;
+        if diff.is_empty() {
+            self.logger.info("Target schema matches the source");
+            return Ok(());
+        }
+
+        self.sync_enums(&diff.enums).await?;
+
+        let mut missing = vec![];
+        let mut changed = vec![];
+        for table in &diff.tables {
+            match table.status.as_str() {
+                "missing" => missing.push(table.table.clone()),
+                "extra" => self.logger.warn(
+                    format!(
+                        "Keeping table {} that only exists in the target",
+                        table.table
+                    )
+                    .as_str(),
+                ),
+                _ => changed.push(table),
+            }
+        }
+
+        // New tables are created like on a fresh copy, referenced tables first
+        let references = get_references(
+            &self.differ.structure_migrator.source_conn,
+            Phase::Structure,
+            self.source_schema(),
+        )
+        .await?;
+        let order = sort_tables(&missing, &references);
+
+        let total = missing.len() + changed.len();
+        let mut failures = vec![];
+        for table in &order.tables {
+            if self.config.run.fail_fast && !failures.is_empty() {
+                self.logger
+                    .error("Stopping after first failure (--fail-fast)");
+                break;
+            }
+            self.logger
+                .info(format!("Creating table {}", table).as_str());
+            let result = self
+                .differ
+                .table_migrator
+                .migrate(self.source_schema(), table)
+                .await;
+            self.record(table, result, &mut failures);
+        }
+        for table in changed {
+            if self.config.run.fail_fast && !failures.is_empty() {
+                self.logger
+                    .error("Stopping after first failure (--fail-fast)");
+                break;
+            }
+            self.logger
+                .info(format!("Altering table {}", table.table).as_str());
+            let result = self.sync_table(table).await;
+            self.record(&table.table, result, &mut failures);
+        }
+
+        if !failures.is_empty() {
+            return Err(CustomError::TablesFailed {
+                phase: Phase::Structure,
+                failed: failures,
+                total,
+            });
+        }
+
+        Ok(())
+    }
+
+    fn record(&self, table: &str, result: CustomResult<()>, failures: &mut Vec<String>) {
+        if let Err(err) = result {
+            self.reporter.failure(table, Phase::Structure, &err);
+            self.logger
+                .error(format!("Failed to sync table {}: {}", table, err).as_str());
+            failures.push(table.to_string());
+        }
+    }
+
+    async fn run(&self, phase: Phase, table: Option<&str>, statement: &str) -> CustomResult<()> {
+        self.logger.debug(statement);
+        execute(
+            &self.differ.structure_migrator.target_conn,
+            phase,
+            statement,
+        )
+        .await
+        .map_err(|err| {
+            let err = CustomError::query(phase, statement, err);
+            match table {
+                Some(table) => err.with_table(table),
+                None => err,
+            }
+        })
+    }
+
+    // Moves source schema qualifiers to the target schema.
+    fn retarget(&self, definition: &str) -> String {
+        let target = format!("{}.", self.target_schema());
+        let ddl = definition
+            .replace(&format!("\"{}\".", self.source_schema()), &target)
+            .replace(&format!("{}.", self.source_schema()), &target);
+        self.differ.table_migrator.clean_type_references(ddl)
+    }
+
+    async fn list_enums(&self, source: bool) -> CustomResult<BTreeMap<String, EnumInfo>> {
+        let migrator = &self.differ.structure_migrator;
+        let (conn, schema) = match source {
+            true => (&migrator.source_conn, self.source_schema()),
+            false => (&migrator.target_conn, self.target_schema()),
+        };
+
+        Ok(migrator
+            .list_all_enums(conn)
+            .await?
+            .into_iter()
+            .filter(|enum_info| enum_info.schema == schema)
+            .map(|enum_info| (enum_info.enum_name.clone(), enum_info))
+            .collect())
+    }
+
+    async fn sync_enums(&self, changes: &[Change]) -> CustomResult<()> {
+        if changes.is_empty() {
+            return Ok(());
+        }
+        let source_enums = self.list_enums(true).await?;
+        let target_enums = self.list_enums(false).await?;
+
+        for change in changes {
+            match change {
+                Change::Missing { name, .. } => {
+                    self.differ
+                        .structure_migrator
+                        .create_enum(&source_enums[name])
+                        .await?
+                }
+                Change::Changed { name, .. } => {
+                    self.add_enum_values(&source_enums[name], &target_enums[name])
+                        .await?
+                }
+                Change::Extra { name, .. } => self
+                    .logger
+                    .warn(format!("Keeping enum {} that only exists in the target", name).as_str()),
+            }
+        }
+
+        Ok(())
+    }
+
+    // Labels can only be added; ones the source no longer has stay in the target.
+    async fn add_enum_values(&self, source: &EnumInfo, target: &EnumInfo) -> CustomResult<()> {
+        let existing: BTreeSet<&String> = target.enum_values.iter().collect();
+        for (position, label) in source.enum_values.iter().enumerate() {
+            if existing.contains(label) {
+                continue;
+            }
+            let placement = match position {
+                0 => format!("BEFORE {}", quote_literal(&target.enum_values[0])),
+                _ => format!("AFTER {}", quote_literal(&source.enum_values[position - 1])),
+            };
+            let statement = format!(
+                "ALTER TYPE {}.{} ADD VALUE IF NOT EXISTS {} {};",
+                self.target_schema(),
+                source.enum_name,
+                quote_literal(label),
+                placement
+            );
+            self.run(Phase::Enum, None, &statement).await?;
+        }
+
+        let source_labels: BTreeSet<&String> = source.enum_values.iter().collect();
+        for label in existing.difference(&source_labels) {
+            self.logger.warn(
+                format!(
+                    "Enum {} keeps value {} that the source no longer has",
+                    source.enum_name, label
+                )
+                .as_str(),
+            );
+        }
+        Ok(())
+    }
+
+    async fn sync_table(&self, table: &TableDiff) -> CustomResult<()> {
+        let migrator = &self.differ.structure_migrator;
+        let table_migrator = &self.differ.table_migrator;
+        let name = table.table.as_str();
+        let qualified = format!("\"{}\".\"{}\"", self.target_schema(), name);
+
+        // Defaults of new columns may use sequences the target doesn't have yet
+        table_migrator
+            .migrate_sequences(self.source_schema(), name)
+            .await?;
+
+        let source_columns = self
+            .column_definitions(&migrator.source_conn, self.source_schema(), name)
+            .await?;
+        let target_columns = self
+            .column_definitions(&migrator.target_conn, self.target_schema(), name)
+            .await?;
+        for change in &table.columns {
+            let statement = match change {
+                Change::Missing { name: column, .. } => format!(
+                    "ALTER TABLE {} ADD COLUMN \"{}\" {};",
+                    qualified,
+                    column,
+                    self.retarget(&source_columns[column].definition)
+                ),
+                Change::Changed { name: column, .. } => {
+                    self.alter_column(&qualified, &source_columns[column], &target_columns[column])
+                }
+                // Dropped once the constraints that may use them are replaced
+                Change::Extra { .. } => continue,
+            };
+            self.run(Phase::Structure, Some(name), &statement).await?;
+        }
+
+        let source_indexes: BTreeMap<String, String> = table_migrator
+            .get_index_ddl(&migrator.source_conn, self.source_schema(), name)
+            .await?
+            .into_iter()
+            .map(|ddl| (index_name(&ddl), ddl))
+            .collect();
+        let source_constraints: BTreeMap<String, String> = table_migrator
+            .get_constraint_ddl(&migrator.source_conn, self.source_schema(), name)
+            .await?
+            .into_iter()
+            .map(|ddl| (table_migrator.extract_constraint_name(&ddl), ddl))
+            .collect();
+
+        for change in &table.constraints {
+            if let Change::Changed {
+                name: constraint, ..
+            } = change
+            {
+                let statement = format!(
+                    "ALTER TABLE {} DROP CONSTRAINT \"{}\";",
+                    qualified, constraint
+                );
+                self.run(Phase::Constraint, Some(name), &statement).await?;
+            }
+        }
+
+        // Indexes backing a primary key or unique constraint come with the constraint
+        for change in &table.indexes {
+            let index = match change {
+                Change::Missing { name: index, .. } | Change::Changed { name: index, .. }
+                    if !source_constraints.contains_key(index) =>
+                {
+                    index
+                }
+                Change::Extra { name: index, .. } => {
+                    self.logger.warn(
+                        format!("Keeping index {} that only exists in the target", index).as_str(),
+                    );
+                    continue;
+                }
+                _ => continue,
+            };
+            if matches!(change, Change::Changed { .. }) {
+                let statement = format!("DROP INDEX \"{}\".\"{}\";", self.target_schema(), index);
+                self.run(Phase::Index, Some(name), &statement).await?;
+            }
+            self.run(
+                Phase::Index,
+                Some(name),
+                &self.retarget(&source_indexes[index]),
+            )
+            .await?;
+        }
+
+        for change in &table.constraints {
+            match change {
+                Change::Missing {
+                    name: constraint, ..
+                }
+                | Change::Changed {
+                    name: constraint, ..
+                } => {
+                    self.run(
+                        Phase::Constraint,
+                        Some(name),
+                        &self.retarget(&source_constraints[constraint]),
+                    )
+                    .await?
+                }
+                Change::Extra {
+                    name: constraint, ..
+                } => self.logger.warn(
+                    format!(
+                        "Keeping constraint {} that only exists in the target",
+                        constraint
+                    )
+                    .as_str(),
+                ),
+            }
+        }
+
+        for change in &table.columns {
+            if let Change::Extra { name: column, .. } = change {
+                let statement = format!("ALTER TABLE {} DROP COLUMN \"{}\";", qualified, column);
+                self.run(Phase::Structure, Some(name), &statement).await?;
+            }
+        }
+
+        Ok(())
+    }
+
+    async fn column_definitions(
+        &self,
+        conn: &sqlx::Pool<sqlx::Postgres>,
+        schema: &str,
+        table: &str,
+    ) -> CustomResult<BTreeMap<String, ColumnDefinition>> {
+        Ok(self
+            .differ
+            .table_migrator
+            .get_column_definitions(conn, schema, table)
+            .await?
+            .into_iter()
+            .map(|column| (column.column_name.clone(), column))
+            .collect())
+    }
+
+    // One statement per column, so its default is dropped before a type change and
+    // set again afterwards.
+    fn alter_column(
+        &self,
+        qualified: &str,
+        source: &ColumnDefinition,
+        target: &ColumnDefinition,
+    ) -> String {
+        let column = &source.column_name;
+        let source_default = source
+            .column_default
+            .as_deref()
+            .map(|default| strip_schema(default, self.source_schema()));
+        let target_default = target
+            .column_default
+            .as_deref()
+            .map(|default| strip_schema(default, self.target_schema()));
+        let type_changed = strip_schema(&source.type_name, self.source_schema())
+            != strip_schema(&target.type_name, self.target_schema());
+        let default_changed = type_changed || source_default != target_default;
+
+        let mut actions = vec![];
+        if default_changed && target_default.is_some() {
+            actions.push(format!("ALTER COLUMN \"{}\" DROP DEFAULT", column));
+        }
+        if type_changed {
+            let type_name = self.retarget(&source.type_name);
+            actions.push(format!(
+                "ALTER COLUMN \"{0}\" TYPE {1} USING \"{0}\"::{1}",
+                column, type_name
+            ));
+        }
+        if let (true, Some(default)) = (default_changed, &source.column_default) {
+            actions.push(format!(
+                "ALTER COLUMN \"{}\" SET DEFAULT {}",
+                column,
+                self.retarget(default)
+            ));
+        }
+        if source.not_null != target.not_null {
+            let action = match source.not_null {
+                true => "SET",
+                false => "DROP",
+            };
+            actions.push(format!("ALTER COLUMN \"{}\" {} NOT NULL", column, action));
+        }
+
+        format!("ALTER TABLE {} {};", qualified, actions.join(", "))
+    }
+}
+
+fn quote_literal(value: &str) -> String {
+    format!("'{}'", value.replace('\'', "''"))
+}
@@ -1,16 +1,113 @@
-use sqlx::{Pool, Postgres, Row};
+use std::time::Instant;
 
+use sqlx::{FromRow, Pool, Postgres, Row};
+
+use crate::checkpoint::Checkpoint;
 use crate::config::Config;
-use crate::error::{CustomError, CustomResult};
+use crate::error::{CustomError, CustomResult, Phase};
 use crate::logger::Logger;
-
-use super::db::get_connections_pool;
+use crate::plan::Plan;
+use crate::report::Reporter;
+
+use super::db::{execute, get_connections_pool};
+
+/// SQLSTATEs of DDL hitting an object that is already there: duplicate_table and
+/// duplicate_object.
+const ALREADY_EXISTS: [&str; 2] = ["42P07", "42710"];
+/// invalid_table_definition, raised among others by a second primary key.
+const INVALID_TABLE_DEFINITION: &str = "42P16";
+
+// Column definitions of table $2 in schema $1 as they go into `CREATE TABLE`, with
+// user-defined types, casts and sequence defaults qualified by $1.
+const COLUMN_DEFINITIONS: &str = r#"
+    column_info AS (
+        SELECT DISTINCT ON (c.column_name)
+            c.column_name,
+            c.table_schema,
+            c.table_name,
+            CASE 
+                WHEN c.data_type = 'USER-DEFINED' THEN
+                    format('%I.%s', $1, 
+                        (SELECT t.typname 
+                         FROM pg_type t 
+                         JOIN pg_namespace n ON t.typnamespace = n.oid 
+                         WHERE t.oid = a.atttypid)
+                    )
+                ELSE c.data_type
+            END as data_type,
+            c.character_maximum_length,
+            c.is_nullable,
+            CASE 
+                WHEN c.column_default LIKE 'nextval(%' THEN
+                    format('nextval(''%I.%s''::regclass)', 
+                        $1, 
+                        regexp_replace(c.column_default, 'nextval\(''([^'']+)''::regclass\)', '\1')
+                    )
+                WHEN c.column_default LIKE '%::%' THEN
+                    regexp_replace(
+                        c.column_default,
+                        '::([^'']+)',
+                        format('::%I.\1', $1)
+                    )
+                ELSE c.column_default
+            END as column_default,
+            c.ordinal_position
+        FROM information_schema.columns c
+        JOIN pg_class cl ON cl.relname = c.table_name
+        JOIN pg_namespace n ON n.nspname = c.table_schema
+        JOIN pg_attribute a ON a.attrelid = cl.oid AND a.attname = c.column_name
+        WHERE c.table_schema = $1 AND c.table_name = $2
+        ORDER BY c.column_name, c.ordinal_position
+    ),
+    column_definition AS (
+        SELECT
+            column_name::text AS column_name,
+            table_schema,
+            table_name,
+            ordinal_position,
+            type_name,
+            is_nullable = 'NO' AS not_null,
+            column_default::text AS column_default,
+            type_name ||
+            CASE 
+                WHEN is_nullable = 'NO' THEN ' NOT NULL'
+                ELSE ''
+            END ||
+            CASE 
+                WHEN column_default IS NOT NULL 
+                THEN ' DEFAULT ' || column_default
+                ELSE ''
+            END AS definition
+        FROM (
+            SELECT
+                *,
+                data_type ||
+                CASE 
+                    WHEN character_maximum_length IS NOT NULL 
+                    THEN '(' || character_maximum_length || ')'
+                    ELSE ''
+                END AS type_name
+            FROM column_info
+        ) typed_column
+    )"#;
+
+/// One column as read by `COLUMN_DEFINITIONS`.
+#[derive(Debug, FromRow)]
+pub struct ColumnDefinition {
+    pub column_name: String,
+    pub type_name: String,
+    pub not_null: bool,
+    pub column_default: Option<String>,
+    /// `type_name`, nullability and default as written in `CREATE TABLE`.
+    pub definition: String,
+}
 
 pub struct TableMigrator {
     pub source_conn: Pool<Postgres>,
     pub target_conn: Pool<Postgres>,
     pub target_schema: String,
     pub logger: Logger,
+    pub reporter: Reporter,
 }
 
 impl TableMigrator {
@@ -24,6 +121,7 @@ impl TableMigrator {
             source_conn,
             target_conn,
             logger,
+            reporter: Reporter::new(),
         })
     }
 }
@@ -33,30 +131,62 @@ impl TableMigrator {
         self.logger
             .info(format!("Cloning table {}.{}", schema, table).as_str());
 
-        // Handle sequences
-        self.migrate_sequences(schema, table).await?;
-
-        // Handle table creation
-        self.migrate_table_structure(schema, table).await?;
+        // Steps finished by an earlier run are skipped with --resume
+        let checkpoint = Checkpoint::new();
+
+        if !checkpoint.is_done(table, Phase::Structure) {
+            // Handle sequences
+            let started = Instant::now();
+            self.migrate_sequences(schema, table).await?;
+            self.reporter
+                .table_phase(table, Phase::Sequence, started.elapsed());
+
+            // Handle table creation
+            let started = Instant::now();
+            self.migrate_table_structure(schema, table).await?;
+            self.reporter
+                .table_phase(table, Phase::Structure, started.elapsed());
+            checkpoint.complete(table, Phase::Structure);
+        }
 
         // Handle partitions
-        self.migrate_partitions(schema, table).await?;
+        if !checkpoint.is_done(table, Phase::Partition) {
+            let started = Instant::now();
+            self.migrate_partitions(schema, table).await?;
+            self.reporter
+                .table_phase(table, Phase::Partition, started.elapsed());
+            checkpoint.complete(table, Phase::Partition);
+        }
 
         // Handle indexes
-        self.migrate_indexes(schema, table).await?;
+        if !checkpoint.is_done(table, Phase::Index) {
+            let started = Instant::now();
+            self.migrate_indexes(schema, table).await?;
+            self.reporter
+                .table_phase(table, Phase::Index, started.elapsed());
+            checkpoint.complete(table, Phase::Index);
+        }
 
         // Handle constraints
-        self.migrate_constraints(schema, table).await?;
+        if !checkpoint.is_done(table, Phase::Constraint) {
+            let started = Instant::now();
+            self.migrate_constraints(schema, table).await?;
+            self.reporter
+                .table_phase(table, Phase::Constraint, started.elapsed());
+            checkpoint.complete(table, Phase::Constraint);
+        }
 
         self.logger
             .debug(format!("Successfully cloned table {}.{}", schema, table).as_str());
         Ok(())
     }
 
-    async fn migrate_sequences(&self, schema: &str, table: &str) -> CustomResult<()> {
+    pub async fn migrate_sequences(&self, schema: &str, table: &str) -> CustomResult<()> {
         self.logger
             .debug(format!("Getting table sequences for table {}.{}", schema, table).as_str());
-        let sequences = self.get_table_sequences(schema, table).await?;
+        let sequences = self
+            .get_table_sequences(&self.source_conn, schema, table)
+            .await?;
         self.logger
             .debug(format!("Got sequences for table {}.{}", schema, table).as_str());
 
@@ -71,7 +201,7 @@ impl TableMigrator {
         Ok(())
     }
 
-    fn extract_sequence_parts<'a>(
+    pub fn extract_sequence_parts<'a>(
         &self,
         sequence: &'a str,
         default_schema: &'a str,
@@ -99,14 +229,13 @@ impl TableMigrator {
 
         self.logger
             .debug(format!("Creating table {}.{}", schema, table).as_str());
-        sqlx::query(&modified_ddl)
-            .execute(&self.target_conn)
+        execute(&self.target_conn, Phase::Structure, &modified_ddl)
             .await
             .map_err(|err| {
                 self.logger
                     .error(format!("Failed to create table: {}", err).as_str());
                 self.logger.error(&modified_ddl);
-                CustomError::QueryExecution
+                CustomError::query(Phase::Structure, &modified_ddl, err).with_table(table)
             })?;
 
         self.logger
@@ -134,14 +263,16 @@ impl TableMigrator {
             .debug(format!("Creating partitions for table {}.{}", schema, table).as_str());
         for partition_ddl in partitions {
             let modified_ddl = self.prepare_ddl(schema, partition_ddl);
-            sqlx::query(&modified_ddl)
-                .execute(&self.target_conn)
-                .await
-                .map_err(|err| {
+            match execute(&self.target_conn, Phase::Partition, &modified_ddl).await {
+                Err(err) if self.created_before(&err) => self.logger.debug(
+                    format!("Partition of table {} already exists, skipping", table).as_str(),
+                ),
+                result => result.map_err(|err| {
                     self.logger
                         .error(format!("Failed to create partition: {}", err).as_str());
-                    CustomError::QueryExecution
-                })?;
+                    CustomError::query(Phase::Partition, &modified_ddl, err).with_table(table)
+                })?,
+            }
         }
         self.logger
             .debug(format!("Created partitions for table {}.{}", schema, table).as_str());
@@ -151,7 +282,7 @@ impl TableMigrator {
     async fn migrate_indexes(&self, schema: &str, table: &str) -> CustomResult<()> {
         self.logger
             .debug(format!("Getting indexes for table {}.{}", schema, table).as_str());
-        let indexes = self.get_index_ddl(schema, table).await?;
+        let indexes = self.get_index_ddl(&self.source_conn, schema, table).await?;
         self.logger
             .debug(format!("Got indexes for table {}.{}", schema, table).as_str());
 
@@ -159,14 +290,16 @@ impl TableMigrator {
             .debug(format!("Creating indexes for table {}.{}", schema, table).as_str());
         for index_ddl in indexes {
             let modified_ddl = self.prepare_ddl(schema, index_ddl);
-            sqlx::query(&modified_ddl)
-                .execute(&self.target_conn)
-                .await
-                .map_err(|err| {
+            match execute(&self.target_conn, Phase::Index, &modified_ddl).await {
+                Err(err) if self.created_before(&err) => self
+                    .logger
+                    .debug(format!("Index of table {} already exists, skipping", table).as_str()),
+                result => result.map_err(|err| {
                     self.logger
                         .error(format!("Failed to create index: {}", err).as_str());
-                    CustomError::QueryExecution
-                })?;
+                    CustomError::query(Phase::Index, &modified_ddl, err).with_table(table)
+                })?,
+            }
         }
         self.logger
             .debug(format!("Created indexes for table {}.{}", schema, table).as_str());
@@ -176,7 +309,9 @@ impl TableMigrator {
     async fn migrate_constraints(&self, schema: &str, table: &str) -> CustomResult<()> {
         self.logger
             .debug(format!("Getting constraints for table {}.{}", schema, table).as_str());
-        let constraints = self.get_constraint_ddl(schema, table).await?;
+        let constraints = self
+            .get_constraint_ddl(&self.source_conn, schema, table)
+            .await?;
         self.logger
             .debug(format!("Got constraints for table {}.{}", schema, table).as_str());
 
@@ -186,7 +321,7 @@ impl TableMigrator {
             let modified_ddl = self.prepare_ddl(schema, constraint_ddl);
             let constraint_name = self.extract_constraint_name(&modified_ddl);
 
-            match sqlx::query(&modified_ddl).execute(&self.target_conn).await {
+            match execute(&self.target_conn, Phase::Constraint, &modified_ddl).await {
                 Ok(_) => self.logger.debug(
                     format!(
                         "Added constraint {} to table {}.{}",
@@ -195,8 +330,11 @@ impl TableMigrator {
                     .as_str(),
                 ),
                 Err(e) => {
-                    // Check if the error is because the constraint already exists
-                    if e.to_string().contains("already exists") {
+                    // Check if the error is because the constraint already exists; a
+                    // primary key under another name fails as a second primary key
+                    let second_primary_key = modified_ddl.contains("PRIMARY KEY")
+                        && self.sqlstate(&e).as_deref() == Some(INVALID_TABLE_DEFINITION);
+                    if self.already_exists(&e) || second_primary_key {
                         self.logger.debug(
                             format!(
                                 "Constraint {} already exists on table {}.{}, skipping",
@@ -209,7 +347,8 @@ impl TableMigrator {
                         self.logger.error(&modified_ddl);
                         self.logger
                             .error(format!("Failed to create constraint: {}", e).as_str());
-                        return Err(CustomError::QueryExecution);
+                        return Err(CustomError::query(Phase::Constraint, &modified_ddl, e)
+                            .with_table(table));
                     }
                 }
             }
@@ -219,6 +358,22 @@ impl TableMigrator {
         Ok(())
     }
 
+    // A step the earlier run failed half-way through left some of its objects behind.
+    fn created_before(&self, err: &sqlx::Error) -> bool {
+        Checkpoint::new().is_resuming() && self.already_exists(err)
+    }
+
+    fn already_exists(&self, err: &sqlx::Error) -> bool {
+        self.sqlstate(err)
+            .is_some_and(|code| ALREADY_EXISTS.contains(&code.as_str()))
+    }
+
+    fn sqlstate(&self, err: &sqlx::Error) -> Option<String> {
+        err.as_database_error()
+            .and_then(|e| e.code())
+            .map(|code| code.into_owned())
+    }
+
     fn prepare_ddl(&self, schema: &str, ddl: String) -> String {
         if schema == "public" {
             ddl.replace("public.", format!("{}.", self.target_schema).as_str())
@@ -231,72 +386,23 @@ impl TableMigrator {
         self.logger
             .debug(format!("Getting DDL for table {}.{}", schema, table).as_str());
 
-        let query = r#"
-            WITH column_info AS (
-                SELECT DISTINCT ON (c.column_name)
-                    c.column_name,
-                    c.table_schema,
-                    c.table_name,
-                    CASE 
-                        WHEN c.data_type = 'USER-DEFINED' THEN
-                            format('%I.%s', $1, 
-                                (SELECT t.typname 
-                                 FROM pg_type t 
-                                 JOIN pg_namespace n ON t.typnamespace = n.oid 
-                                 WHERE t.oid = a.atttypid)
-                            )
-                        ELSE c.data_type
-                    END as data_type,
-                    c.character_maximum_length,
-                    c.is_nullable,
-                    CASE 
-                        WHEN c.column_default LIKE 'nextval(%' THEN
-                            format('nextval(''%I.%s''::regclass)', 
-                                $1, 
-                                regexp_replace(c.column_default, 'nextval\(''([^'']+)''::regclass\)', '\1')
-                            )
-                        WHEN c.column_default LIKE '%::%' THEN
-                            regexp_replace(
-                                c.column_default,
-                                '::([^'']+)',
-                                format('::%I.\1', $1)
-                            )
-                        ELSE c.column_default
-                    END as column_default,
-                    c.ordinal_position
-                FROM information_schema.columns c
-                JOIN pg_class cl ON cl.relname = c.table_name
-                JOIN pg_namespace n ON n.nspname = c.table_schema
-                JOIN pg_attribute a ON a.attrelid = cl.oid AND a.attname = c.column_name
-                WHERE c.table_schema = $1 AND c.table_name = $2
-                ORDER BY c.column_name, c.ordinal_position
-            )
+        let query = format!(
+            r#"
+            WITH {}
             SELECT 
                 'CREATE TABLE ' || quote_ident($1) || '.' || quote_ident($2) || ' (' ||
                 string_agg(
-                    quote_ident(column_name) || ' ' || data_type ||
-                    CASE 
-                        WHEN character_maximum_length IS NOT NULL 
-                        THEN '(' || character_maximum_length || ')'
-                        ELSE ''
-                    END ||
-                    CASE 
-                        WHEN is_nullable = 'NO' THEN ' NOT NULL'
-                        ELSE ''
-                    END ||
-                    CASE 
-                        WHEN column_default IS NOT NULL 
-                        THEN ' DEFAULT ' || column_default
-                        ELSE ''
-                    END,
+                    quote_ident(column_name) || ' ' || definition,
                     ', '
                     ORDER BY ordinal_position
                 ) || ');'
-            FROM column_info
+            FROM column_definition
             GROUP BY table_schema, table_name;
-        "#;
+        "#,
+            COLUMN_DEFINITIONS
+        );
 
-        let ddl = sqlx::query_scalar::<_, Option<String>>(query)
+        let ddl = sqlx::query_scalar::<_, Option<String>>(&query)
             .bind(schema)
             .bind(table)
             .fetch_optional(&self.source_conn)
@@ -304,8 +410,8 @@ impl TableMigrator {
             .map_err(|err| {
                 self.logger
                     .error(format!("Failed to get table DDL: {}", err).as_str());
-                self.logger.error(query);
-                CustomError::QueryExecution
+                self.logger.error(&query);
+                CustomError::query(Phase::Structure, &query, err).with_table(table)
             })?;
 
         let ddl_str = match ddl {
@@ -313,14 +419,22 @@ impl TableMigrator {
             _ => {
                 self.logger
                     .error(format!("No DDL returned for table {}.{}", schema, table).as_str());
-                return Err(CustomError::DbTableStructure);
+                return Err(CustomError::structure(
+                    Phase::Structure,
+                    table,
+                    format!("no DDL returned for {}.{}", schema, table),
+                ));
             }
         };
 
         if ddl_str.is_empty() {
             self.logger
                 .error(format!("Empty DDL returned for table {}.{}", schema, table).as_str());
-            return Err(CustomError::DbTableStructure);
+            return Err(CustomError::structure(
+                Phase::Structure,
+                table,
+                format!("empty DDL returned for {}.{}", schema, table),
+            ));
         }
 
         self.logger
@@ -329,6 +443,36 @@ impl TableMigrator {
         Ok(ddl_str)
     }
 
+    /// Type, nullability and default of every column of a table.
+    pub async fn get_column_definitions(
+        &self,
+        conn: &Pool<Postgres>,
+        schema: &str,
+        table: &str,
+    ) -> CustomResult<Vec<ColumnDefinition>> {
+        let query = format!(
+            r#"
+            WITH {}
+            SELECT column_name, type_name, not_null, column_default, definition
+            FROM column_definition
+            ORDER BY ordinal_position;
+        "#,
+            COLUMN_DEFINITIONS
+        );
+
+        sqlx::query_as(&query)
+            .bind(schema)
+            .bind(table)
+            .fetch_all(conn)
+            .await
+            .map_err(|err| {
+                self.logger
+                    .error(format!("Failed to get column definitions: {}", err).as_str());
+                self.logger.error(&query);
+                CustomError::query(Phase::Structure, &query, err).with_table(table)
+            })
+    }
+
     async fn get_partition_ddl(&self, schema: &str, table: &str) -> CustomResult<Vec<String>> {
         let query = r#"
             SELECT 
@@ -352,13 +496,18 @@ impl TableMigrator {
                 self.logger
                     .error(format!("Failed to get partitions: {}", err).as_str());
                 self.logger.error(query);
-                CustomError::QueryExecution
+                CustomError::query(Phase::Partition, query, err).with_table(table)
             })?;
 
         Ok(partitions)
     }
 
-    async fn get_index_ddl(&self, schema: &str, table: &str) -> CustomResult<Vec<String>> {
+    pub async fn get_index_ddl(
+        &self,
+        conn: &Pool<Postgres>,
+        schema: &str,
+        table: &str,
+    ) -> CustomResult<Vec<String>> {
         let query = r#"
             SELECT 
                 indexdef || ';' as index_ddl
@@ -369,19 +518,24 @@ impl TableMigrator {
         let indexes: Vec<String> = sqlx::query_scalar(query)
             .bind(schema)
             .bind(table)
-            .fetch_all(&self.source_conn)
+            .fetch_all(conn)
             .await
             .map_err(|err| {
                 self.logger
                     .error(format!("Failed to get indexes: {}", err).as_str());
                 self.logger.error(query);
-                CustomError::QueryExecution
+                CustomError::query(Phase::Index, query, err).with_table(table)
             })?;
 
         Ok(indexes)
     }
 
-    async fn get_constraint_ddl(&self, schema: &str, table: &str) -> CustomResult<Vec<String>> {
+    pub async fn get_constraint_ddl(
+        &self,
+        conn: &Pool<Postgres>,
+        schema: &str,
+        table: &str,
+    ) -> CustomResult<Vec<String>> {
         let query = r#"
         WITH constraint_info AS (
             SELECT 
@@ -434,19 +588,25 @@ impl TableMigrator {
         let constraints: Vec<String> = sqlx::query_scalar(query)
             .bind(schema)
             .bind(table)
-            .fetch_all(&self.source_conn)
+            .fetch_all(conn)
             .await
             .map_err(|err| {
                 self.logger
                     .error(format!("Failed to get constraints: {}", err).as_str());
                 self.logger.error(query);
-                CustomError::QueryExecution
+                CustomError::query(Phase::Constraint, query, err).with_table(table)
             })?;
 
         Ok(constraints)
     }
 
-    async fn get_sequence_ddl(&self, schema: &str, sequence: &str) -> CustomResult<String> {
+    /// `CREATE SEQUENCE` statement of a sequence, `None` when it doesn't exist.
+    pub async fn get_sequence_definition(
+        &self,
+        conn: &Pool<Postgres>,
+        schema: &str,
+        sequence: &str,
+    ) -> CustomResult<Option<String>> {
         let query = r#"
             SELECT 
                 'CREATE SEQUENCE ' || quote_ident($1) || '.' || quote_ident($2) ||
@@ -458,17 +618,24 @@ impl TableMigrator {
             WHERE sequence_schema = $1 AND sequence_name = $2;
         "#;
 
-        match sqlx::query_scalar(query)
+        sqlx::query_scalar(query)
             .bind(schema)
             .bind(sequence)
-            .fetch_optional(&self.source_conn)
+            .fetch_optional(conn)
             .await
             .map_err(|err| {
                 self.logger
                     .error(format!("Failed to get sequence DDL: {}", err).as_str());
                 self.logger.error(query);
-                CustomError::QueryExecution
-            })? {
+                CustomError::query(Phase::Sequence, query, err)
+            })
+    }
+
+    async fn get_sequence_ddl(&self, schema: &str, sequence: &str) -> CustomResult<String> {
+        match self
+            .get_sequence_definition(&self.source_conn, schema, sequence)
+            .await?
+        {
             Some(ddl) => {
                 self.logger
                     .debug(format!("Got sequence DDL: {}", ddl).as_str());
@@ -495,11 +662,7 @@ impl TableMigrator {
     }
 
     async fn create_sequence(&self, schema: &str, sequence: &str) -> CustomResult<()> {
-        // Clean up the sequence name if it contains nextval or regclass
-        let clean_sequence = sequence
-            .replace("nextval('", "")
-            .replace("'::regclass)", "")
-            .replace("'", "");
+        let clean_sequence = self.clean_sequence_name(sequence);
 
         self.logger.debug(
             format!(
@@ -527,10 +690,11 @@ impl TableMigrator {
                 self.logger
                     .error(format!("Failed to check sequence existence: {}", err).as_str());
                 self.logger.error(sequence_exists_query);
-                CustomError::QueryExecution
+                CustomError::query(Phase::Sequence, sequence_exists_query, err)
             })?;
 
-        if sequence_exists {
+        // On --dry-run the schema drop is only planned, so existing sequences don't count.
+        if sequence_exists && !Plan::new().is_dry_run() {
             self.logger.debug(
                 format!(
                     "Sequence {}.{} already exists, skipping",
@@ -555,14 +719,13 @@ impl TableMigrator {
             .debug(format!("Modified sequence DDL: {}", modified_ddl).as_str());
 
         // Create sequence in target
-        sqlx::query(&modified_ddl)
-            .execute(&self.target_conn)
+        execute(&self.target_conn, Phase::Sequence, &modified_ddl)
             .await
             .map_err(|err| {
                 self.logger
                     .error(format!("Failed to create sequence: {}", err).as_str());
                 self.logger.error(&modified_ddl);
-                CustomError::QueryExecution
+                CustomError::query(Phase::Sequence, &modified_ddl, err)
             })?;
 
         self.logger
@@ -570,7 +733,20 @@ impl TableMigrator {
         Ok(())
     }
 
-    async fn get_table_sequences(&self, schema: &str, table: &str) -> CustomResult<Vec<String>> {
+    /// Strips `nextval('...'::regclass)` around a sequence name.
+    pub fn clean_sequence_name(&self, sequence: &str) -> String {
+        sequence
+            .replace("nextval('", "")
+            .replace("'::regclass)", "")
+            .replace("'", "")
+    }
+
+    pub async fn get_table_sequences(
+        &self,
+        conn: &Pool<Postgres>,
+        schema: &str,
+        table: &str,
+    ) -> CustomResult<Vec<String>> {
         let query = r#"
             SELECT DISTINCT
                 column_default,
@@ -595,13 +771,13 @@ impl TableMigrator {
         let rows = sqlx::query(query)
             .bind(schema)
             .bind(table)
-            .fetch_all(&self.source_conn)
+            .fetch_all(conn)
             .await
             .map_err(|err| {
                 self.logger
                     .error(format!("Failed to get table sequences: {}", err).as_str());
                 self.logger.error(query);
-                CustomError::QueryExecution
+                CustomError::query(Phase::Sequence, query, err).with_table(table)
             })?;
 
         let mut sequences = Vec::new();
@@ -630,7 +806,7 @@ impl TableMigrator {
         Ok(sequences)
     }
 
-    fn clean_type_references(&self, ddl: String) -> String {
+    pub fn clean_type_references(&self, ddl: String) -> String {
         let mut cleaned_ddl = ddl;
         let built_in_types = [
             "text",
@@ -675,7 +851,7 @@ impl TableMigrator {
     }
 
     // Helper function to extract constraint name from DDL
-    fn extract_constraint_name(&self, ddl: &str) -> String {
+    pub fn extract_constraint_name(&self, ddl: &str) -> String {
         // First try to extract the constraint name directly
         if let Some(start) = ddl.find("CONSTRAINT ") {
             if let Some(end) = ddl[start..].find(" ") {
@@ -0,0 +1,264 @@
+/// How a column is read from the source and written back as a SQL literal,
+/// keyed on `information_schema.columns.data_type`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ValueKind {
+    Integer,
+    Boolean,
+    Bytea,
+    /// Everything with a lossless text form: numeric/float (incl. NaN, Infinity),
+    /// json/jsonb, ARRAY, range, geometric, uuid, date/time, network and
+    /// USER-DEFINED enum or composite types.
+    Text,
+}
+
+impl ValueKind {
+    pub fn from_data_type(data_type: &str) -> Self {
+        match data_type {
+            "smallint" | "integer" | "bigint" => Self::Integer,
+            "boolean" => Self::Boolean,
+            "bytea" => Self::Bytea,
+            _ => Self::Text,
+        }
+    }
+
+    /// Select-list entry returning the column as text under its own name.
+    pub fn select_expression(&self, column: &str) -> String {
+        let column = format!("\"{}\"", column);
+        match self {
+            // Hex regardless of the server's `bytea_output` setting.
+            Self::Bytea => format!("encode({0}, 'hex') AS {0}", column),
+            _ => format!("{0}::text AS {0}", column),
+        }
+    }
+
+    /// Literal for a value produced by `select_expression`. Quoted literals are left
+    /// untyped, so the target coerces them to the column type (including enums and
+    /// arrays living in the target schema).
+    pub fn literal(&self, value: Option<&str>) -> String {
+        match (self, value) {
+            (_, None) => "NULL".to_string(),
+            (Self::Integer | Self::Boolean, Some(value)) => value.to_string(),
+            (Self::Bytea, Some(value)) => format!("'\\x{}'", value),
+            (Self::Text, Some(value)) => format!("'{}'", value.replace('\'', "''")),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::ValueKind;
+
+    /// Maps `data_type`, then checks the select-list entry and the literal written
+    /// back for a value in the form that entry returns.
+    fn check(data_type: &str, kind: ValueKind, select: &str, value: Option<&str>, literal: &str) {
+        let mapped = ValueKind::from_data_type(data_type);
+        assert_eq!(mapped, kind, "kind of {}", data_type);
+        assert_eq!(
+            mapped.select_expression("col"),
+            select,
+            "select of {}",
+            data_type
+        );
+        assert_eq!(mapped.literal(value), literal, "literal of {}", data_type);
+    }
+
+    const TEXT_SELECT: &str = "\"col\"::text AS \"col\"";
+
+    #[test]
+    fn arrays() {
+        check(
+            "ARRAY",
+            ValueKind::Text,
+            TEXT_SELECT,
+            Some("{1,2,NULL}"),
+            "'{1,2,NULL}'",
+        );
+        check(
+            "ARRAY",
+            ValueKind::Text,
+            TEXT_SELECT,
+            Some("{\"it's\",b}"),
+            "'{\"it''s\",b}'",
+        );
+    }
+
+    #[test]
+    fn composite_and_enum() {
+        check(
+            "USER-DEFINED",
+            ValueKind::Text,
+            TEXT_SELECT,
+            Some("(1,\"O'Brien\")"),
+            "'(1,\"O''Brien\")'",
+        );
+        check(
+            "USER-DEFINED",
+            ValueKind::Text,
+            TEXT_SELECT,
+            Some("active"),
+            "'active'",
+        );
+    }
+
+    #[test]
+    fn ranges() {
+        check(
+            "int4range",
+            ValueKind::Text,
+            TEXT_SELECT,
+            Some("[1,10)"),
+            "'[1,10)'",
+        );
+        check(
+            "tstzrange",
+            ValueKind::Text,
+            TEXT_SELECT,
+            Some("[\"2024-01-01 00:00:00+00\",)"),
+            "'[\"2024-01-01 00:00:00+00\",)'",
+        );
+    }
+
+    #[test]
+    fn json_and_jsonb() {
+        for data_type in ["json", "jsonb"] {
+            check(
+                data_type,
+                ValueKind::Text,
+                TEXT_SELECT,
+                Some("{\"name\": \"it's\"}"),
+                "'{\"name\": \"it''s\"}'",
+            );
+        }
+    }
+
+    #[test]
+    fn bytea_round_trips_as_hex() {
+        check(
+            "bytea",
+            ValueKind::Bytea,
+            "encode(\"col\", 'hex') AS \"col\"",
+            Some("00ff27"),
+            "'\\x00ff27'",
+        );
+        check(
+            "bytea",
+            ValueKind::Bytea,
+            "encode(\"col\", 'hex') AS \"col\"",
+            Some(""),
+            "'\\x'",
+        );
+    }
+
+    #[test]
+    fn geometric() {
+        check(
+            "point",
+            ValueKind::Text,
+            TEXT_SELECT,
+            Some("(1,2)"),
+            "'(1,2)'",
+        );
+        check(
+            "polygon",
+            ValueKind::Text,
+            TEXT_SELECT,
+            Some("((0,0),(1,1),(1,0))"),
+            "'((0,0),(1,1),(1,0))'",
+        );
+    }
+
+    #[test]
+    fn numeric_and_floats() {
+        check(
+            "numeric",
+            ValueKind::Text,
+            TEXT_SELECT,
+            Some("12345678901234567890.0001"),
+            "'12345678901234567890.0001'",
+        );
+        check(
+            "numeric",
+            ValueKind::Text,
+            TEXT_SELECT,
+            Some("NaN"),
+            "'NaN'",
+        );
+        check(
+            "double precision",
+            ValueKind::Text,
+            TEXT_SELECT,
+            Some("-Infinity"),
+            "'-Infinity'",
+        );
+    }
+
+    #[test]
+    fn uuid() {
+        check(
+            "uuid",
+            ValueKind::Text,
+            TEXT_SELECT,
+            Some("a0eebc99-9c0b-4ef8-bb6d-6bb9bd380a11"),
+            "'a0eebc99-9c0b-4ef8-bb6d-6bb9bd380a11'",
+        );
+    }
+
+    #[test]
+    fn timestamps() {
+        check(
+            "timestamp without time zone",
+            ValueKind::Text,
+            TEXT_SELECT,
+            Some("2024-02-29 12:34:56.789"),
+            "'2024-02-29 12:34:56.789'",
+        );
+        check(
+            "timestamp with time zone",
+            ValueKind::Text,
+            TEXT_SELECT,
+            Some("2024-02-29 12:34:56.789+01"),
+            "'2024-02-29 12:34:56.789+01'",
+        );
+    }
+
+    #[test]
+    fn integers_and_booleans() {
+        for data_type in ["smallint", "integer", "bigint"] {
+            check(
+                data_type,
+                ValueKind::Integer,
+                TEXT_SELECT,
+                Some("-42"),
+                "-42",
+            );
+        }
+        check(
+            "boolean",
+            ValueKind::Boolean,
+            TEXT_SELECT,
+            Some("true"),
+            "true",
+        );
+        check(
+            "boolean",
+            ValueKind::Boolean,
+            TEXT_SELECT,
+            Some("false"),
+            "false",
+        );
+    }
+
+    #[test]
+    fn nulls() {
+        for data_type in [
+            "integer",
+            "boolean",
+            "bytea",
+            "jsonb",
+            "ARRAY",
+            "USER-DEFINED",
+        ] {
+            assert_eq!(ValueKind::from_data_type(data_type).literal(None), "NULL");
+        }
+    }
+}
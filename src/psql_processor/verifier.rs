@@ -0,0 +1,301 @@
+use std::collections::BTreeMap;
+use std::time::Instant;
+
+use sqlx::{Pool, Postgres};
+
+use crate::config::Config;
+use crate::error::{CustomError, CustomResult, Phase};
+use crate::logger::Logger;
+use crate::parallel::run_async;
+use crate::report::Reporter;
+use crate::verify::{
+    compare, compare_columns, differing_buckets, differing_keys, Summary, BUCKET_DIGITS,
+};
+
+use super::db::get_connections_pool;
+
+/// Compares every copied table of source and target by row count and checksum.
+pub struct Verifier {
+    pub config: Config,
+    pub target_schema: String,
+    pub source_schema: String,
+    pub source_conn: Pool<Postgres>,
+    pub target_conn: Pool<Postgres>,
+    pub logger: Logger,
+    pub reporter: Reporter,
+}
+
+impl Verifier {
+    pub async fn new(config: Config) -> CustomResult<Self> {
+        let logger = Logger::new();
+        let source_conn = get_connections_pool(&config.source).await?;
+        let target_conn = get_connections_pool(&config.target).await?;
+
+        Ok(Self {
+            config: config.clone(),
+            target_schema: config.target.schema.clone().unwrap(),
+            source_schema: config.source.schema.clone().unwrap(),
+            source_conn,
+            target_conn,
+            logger,
+            reporter: Reporter::new(),
+        })
+    }
+
+    pub async fn verify(&self) -> CustomResult<()> {
+        let tables = &self.config.tables.data_source;
+        let mut failed_tables = Vec::new();
+        run_async(
+            tables,
+            self.config.workers(),
+            |table| self.verify_table(table),
+            |table, result| {
+                if let Err(err) = result {
+                    self.reporter.failure(table, Phase::Verify, &err);
+                    self.logger
+                        .error(format!("Table {} differs: {}", table, err).as_str());
+                    failed_tables.push(table.to_string());
+                }
+                true
+            },
+        )
+        .await;
+
+        if !failed_tables.is_empty() {
+            return Err(CustomError::TablesFailed {
+                phase: Phase::Verify,
+                failed: failed_tables,
+                total: tables.len(),
+            });
+        }
+        self.logger
+            .info(format!("All {} tables match", tables.len()).as_str());
+        Ok(())
+    }
+
+    async fn verify_table(&self, table: &str) -> CustomResult<()> {
+        let started = Instant::now();
+        let result = self.compare_table(table).await;
+        self.reporter
+            .table_phase(table, Phase::Verify, started.elapsed());
+
+        result
+    }
+
+    async fn compare_table(&self, table: &str) -> CustomResult<()> {
+        let columns = self
+            .get_columns(&self.source_conn, &self.source_schema, table)
+            .await?;
+        let target_columns = self
+            .get_columns(&self.target_conn, &self.target_schema, table)
+            .await?;
+        if let Some(difference) = compare_columns(&columns, &target_columns) {
+            return Err(CustomError::structure(Phase::Verify, table, difference));
+        }
+
+        // Columns in name order, rendered by their text output
+        let row = format!(
+            "md5(ROW({})::text)",
+            columns
+                .iter()
+                .map(|column| format!("\"{}\"", column))
+                .collect::<Vec<String>>()
+                .join(", ")
+        );
+        let source = self
+            .summarize(&self.source_conn, &self.source_schema, table, &row)
+            .await?;
+        let target = self
+            .summarize(&self.target_conn, &self.target_schema, table, &row)
+            .await?;
+
+        let Some(mut difference) = compare(&source, &target) else {
+            self.logger
+                .info(format!("Table {} matches: {} rows", table, source.rows).as_str());
+            return Ok(());
+        };
+        if self.config.verify.sample_keys > 0 {
+            difference = format!("{}; {}", difference, self.sample_keys(table, &row).await?);
+        }
+
+        Err(CustomError::structure(Phase::Verify, table, difference))
+    }
+
+    async fn get_columns(
+        &self,
+        conn: &Pool<Postgres>,
+        schema: &str,
+        table: &str,
+    ) -> CustomResult<Vec<String>> {
+        let query = r#"
+            SELECT column_name::text
+            FROM information_schema.columns
+            WHERE table_schema = $1
+            AND table_name = $2
+            ORDER BY column_name;
+        "#;
+
+        sqlx::query_scalar(query)
+            .bind(schema)
+            .bind(table)
+            .fetch_all(conn)
+            .await
+            .map_err(|err| CustomError::query(Phase::Verify, query, err).with_table(table))
+    }
+
+    fn checksum(&self, row: &str) -> String {
+        match self.config.verify.checksums {
+            true => format!(
+                "coalesce(sum(('x' || left({}, 16))::bit(64)::bigint), 0)::text",
+                row
+            ),
+            false => "NULL::text".to_string(),
+        }
+    }
+
+    async fn summarize(
+        &self,
+        conn: &Pool<Postgres>,
+        schema: &str,
+        table: &str,
+        row: &str,
+    ) -> CustomResult<Summary> {
+        let query = format!(
+            "SELECT count(*), {} FROM \"{}\".\"{}\"",
+            self.checksum(row),
+            schema,
+            table
+        );
+
+        let (rows, checksum): (i64, Option<String>) = sqlx::query_as(&query)
+            .fetch_one(conn)
+            .await
+            .map_err(|err| CustomError::query(Phase::Verify, &query, err).with_table(table))?;
+        Ok(Summary { rows, checksum })
+    }
+
+    // Rows are spread over buckets by their key, so only the buckets that differ
+    // are read row by row.
+    async fn sample_keys(&self, table: &str, row: &str) -> CustomResult<String> {
+        let key_columns = self.get_primary_key(table).await?;
+        if key_columns.is_empty() {
+            return Ok("no primary key to sample".to_string());
+        }
+        let key = format!(
+            "ROW({})::text",
+            key_columns
+                .iter()
+                .map(|column| format!("\"{}\"", column))
+                .collect::<Vec<String>>()
+                .join(", ")
+        );
+
+        let source = self
+            .summarize_buckets(&self.source_conn, &self.source_schema, table, &key, row)
+            .await?;
+        let target = self
+            .summarize_buckets(&self.target_conn, &self.target_schema, table, &key, row)
+            .await?;
+
+        let limit = self.config.verify.sample_keys;
+        let mut keys = Vec::new();
+        for bucket in differing_buckets(&source, &target) {
+            let source_rows = self
+                .read_bucket(
+                    &self.source_conn,
+                    &self.source_schema,
+                    table,
+                    &key,
+                    row,
+                    &bucket,
+                )
+                .await?;
+            let target_rows = self
+                .read_bucket(
+                    &self.target_conn,
+                    &self.target_schema,
+                    table,
+                    &key,
+                    row,
+                    &bucket,
+                )
+                .await?;
+            keys.extend(differing_keys(
+                &source_rows,
+                &target_rows,
+                limit - keys.len(),
+            ));
+            if keys.len() >= limit {
+                break;
+            }
+        }
+
+        Ok(format!("differing keys: {}", keys.join(", ")))
+    }
+
+    async fn get_primary_key(&self, table: &str) -> CustomResult<Vec<String>> {
+        let query = r#"
+            SELECT a.attname::text
+            FROM pg_index i
+            JOIN pg_attribute a ON a.attrelid = i.indrelid AND a.attnum = ANY(i.indkey)
+            WHERE i.indrelid = to_regclass($1)
+            AND i.indisprimary
+            ORDER BY array_position(i.indkey::int2[], a.attnum);
+        "#;
+
+        sqlx::query_scalar(query)
+            .bind(format!("\"{}\".\"{}\"", self.source_schema, table))
+            .fetch_all(&self.source_conn)
+            .await
+            .map_err(|err| CustomError::query(Phase::Verify, query, err).with_table(table))
+    }
+
+    async fn summarize_buckets(
+        &self,
+        conn: &Pool<Postgres>,
+        schema: &str,
+        table: &str,
+        key: &str,
+        row: &str,
+    ) -> CustomResult<BTreeMap<String, Summary>> {
+        let query = format!(
+            "SELECT left(md5({}), {}), count(*), {} FROM \"{}\".\"{}\" GROUP BY 1",
+            key,
+            BUCKET_DIGITS,
+            self.checksum(row),
+            schema,
+            table
+        );
+
+        let buckets: Vec<(String, i64, Option<String>)> = sqlx::query_as(&query)
+            .fetch_all(conn)
+            .await
+            .map_err(|err| CustomError::query(Phase::Verify, &query, err).with_table(table))?;
+        Ok(buckets
+            .into_iter()
+            .map(|(bucket, rows, checksum)| (bucket, Summary { rows, checksum }))
+            .collect())
+    }
+
+    async fn read_bucket(
+        &self,
+        conn: &Pool<Postgres>,
+        schema: &str,
+        table: &str,
+        key: &str,
+        row: &str,
+        bucket: &str,
+    ) -> CustomResult<BTreeMap<String, String>> {
+        let query = format!(
+            "SELECT {0}, {1} FROM \"{2}\".\"{3}\" WHERE left(md5({0}), {4}) = $1",
+            key, row, schema, table, BUCKET_DIGITS
+        );
+
+        let rows: Vec<(String, String)> = sqlx::query_as(&query)
+            .bind(bucket)
+            .fetch_all(conn)
+            .await
+            .map_err(|err| CustomError::query(Phase::Verify, &query, err).with_table(table))?;
+        Ok(rows.into_iter().collect())
+    }
+}
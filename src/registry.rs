@@ -0,0 +1,53 @@
+use std::future::Future;
+use std::pin::Pin;
+
+use crate::config::{Config, Engine};
+use crate::diff::{self, DiffFormat};
+use crate::error::{CustomError, CustomResult};
+use crate::mysql_processor::migrator::Migrator as MysqlMigrator;
+use crate::psql_processor::migrator::Migrator as PsqlMigrator;
+use crate::traits::MigratorTrait;
+
+pub type MigrationFuture = Pin<Box<dyn Future<Output = CustomResult<()>>>>;
+pub type MigrationRunner = fn(Config, Task) -> MigrationFuture;
+
+/// What a run does with the migrator of its engine.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Task {
+    Migrate,
+    Verify,
+    Diff(DiffFormat),
+}
+
+// To add an engine: implement `MigratorTrait` for its migrator, add a variant to
+// `config::Engine` and register it here.
+const ENGINES: &[(Engine, MigrationRunner)] = &[
+    (Engine::Mysql, run::<MysqlMigrator>),
+    (Engine::Postgres, run::<PsqlMigrator>),
+];
+
+fn run<M: MigratorTrait + 'static>(config: Config, task: Task) -> MigrationFuture {
+    Box::pin(async move {
+        let migrator = M::new(config);
+        match task {
+            Task::Migrate => migrator.migrate().await,
+            Task::Verify => migrator.verify().await,
+            Task::Diff(format) => diff::print(&migrator.diff().await?, format),
+        }
+    })
+}
+
+pub fn get_runner(engine: Engine) -> CustomResult<MigrationRunner> {
+    ENGINES
+        .iter()
+        .find(|(registered, _)| *registered == engine)
+        .map(|(_, runner)| *runner)
+        .ok_or_else(|| {
+            let supported: Vec<String> = ENGINES.iter().map(|(e, _)| e.to_string()).collect();
+            CustomError::UnsupportedEngine(format!(
+                "no migrator registered for `{}`; supported engines: {}",
+                engine,
+                supported.join(", ")
+            ))
+        })
+}
@@ -0,0 +1,209 @@
+use std::collections::{BTreeMap, BTreeSet};
+use std::fs;
+use std::sync::Mutex;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+use serde_derive::Serialize;
+
+use crate::error::{CustomError, CustomResult, Phase};
+use crate::logger::Logger;
+
+static REPORT: Mutex<Option<ReportState>> = Mutex::new(None);
+
+struct ReportState {
+    path: Option<String>,
+    started: Instant,
+    report: RunReport,
+}
+
+#[derive(Debug, Serialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum SkipReason {
+    /// Name matches the staging/partition table pattern.
+    Pattern,
+    /// Framework bookkeeping table such as `schema_migrations`.
+    Private,
+    /// Listed in `tables.skip`.
+    Config,
+}
+
+#[derive(Debug, Serialize, Default)]
+struct TableReport {
+    phases_ms: BTreeMap<String, u128>,
+    rows_read: u64,
+    rows_written: u64,
+    bytes: u64,
+}
+
+#[derive(Debug, Serialize)]
+struct SkippedTable {
+    table: String,
+    reason: SkipReason,
+}
+
+#[derive(Debug, Serialize)]
+struct Failure {
+    table: Option<String>,
+    phase: Option<String>,
+    error: String,
+}
+
+#[derive(Debug, Serialize, Default)]
+struct Totals {
+    tables: usize,
+    failed_tables: usize,
+    skipped_tables: usize,
+    rows_read: u64,
+    rows_written: u64,
+    bytes: u64,
+}
+
+#[derive(Debug, Serialize, Default)]
+struct RunReport {
+    engine: String,
+    started_at_unix: u64,
+    duration_ms: u128,
+    status: String,
+    exit_code: u8,
+    phases_ms: BTreeMap<String, u128>,
+    tables: BTreeMap<String, TableReport>,
+    skipped: Vec<SkippedTable>,
+    failures: Vec<Failure>,
+    totals: Totals,
+}
+
+/// Collects the outcome of a run; written as JSON when `--report` is given.
+#[derive(Debug)]
+pub struct Reporter {}
+
+impl Reporter {
+    pub fn new() -> Self {
+        Self {}
+    }
+
+    pub fn init(path: Option<String>, engine: &str) {
+        let started_at_unix = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|elapsed| elapsed.as_secs())
+            .unwrap_or(0);
+
+        let report = RunReport {
+            engine: engine.to_string(),
+            started_at_unix,
+            ..RunReport::default()
+        };
+
+        *REPORT.lock().unwrap() = Some(ReportState {
+            path,
+            started: Instant::now(),
+            report,
+        });
+    }
+
+    fn with_report(&self, update: impl FnOnce(&mut RunReport)) {
+        if let Some(state) = REPORT.lock().unwrap().as_mut() {
+            update(&mut state.report);
+        }
+    }
+
+    /// Wall time of a whole run step (structure, data).
+    pub fn run_phase(&self, phase: Phase, elapsed: Duration) {
+        self.with_report(|report| {
+            *report.phases_ms.entry(phase.to_string()).or_default() += elapsed.as_millis();
+        });
+    }
+
+    pub fn table_phase(&self, table: &str, phase: Phase, elapsed: Duration) {
+        self.with_report(|report| {
+            let table = report.tables.entry(table.to_string()).or_default();
+            *table.phases_ms.entry(phase.to_string()).or_default() += elapsed.as_millis();
+        });
+    }
+
+    pub fn rows(&self, table: &str, read: u64, written: u64, bytes: u64) {
+        self.with_report(|report| {
+            let table = report.tables.entry(table.to_string()).or_default();
+            table.rows_read += read;
+            table.rows_written += written;
+            table.bytes += bytes;
+        });
+    }
+
+    pub fn skipped(&self, table: &str, reason: SkipReason) {
+        self.with_report(|report| {
+            report.skipped.push(SkippedTable {
+                table: table.to_string(),
+                reason,
+            });
+        });
+    }
+
+    pub fn failure(&self, table: &str, phase: Phase, err: &CustomError) {
+        self.with_report(|report| {
+            report.failures.push(Failure {
+                table: Some(table.to_string()),
+                phase: Some(err.phase().unwrap_or(phase).to_string()),
+                error: err.to_string(),
+            });
+        });
+    }
+
+    /// Closes the report and writes it to the `--report` path, if any.
+    pub fn finish(&self, result: &CustomResult<()>) {
+        let logger = Logger::new();
+        let Some(mut state) = REPORT.lock().unwrap().take() else {
+            return;
+        };
+        let report = &mut state.report;
+
+        report.duration_ms = state.started.elapsed().as_millis();
+        match result {
+            Ok(()) => report.status = "success".to_string(),
+            Err(err) => {
+                report.exit_code = err.exit_code();
+                report.status = match err {
+                    CustomError::TablesFailed { failed, total, .. } if failed.len() < *total => {
+                        "partial_failure".to_string()
+                    }
+                    _ => "failure".to_string(),
+                };
+                // Table failures are already listed individually.
+                if !matches!(err, CustomError::TablesFailed { .. }) {
+                    report.failures.push(Failure {
+                        table: None,
+                        phase: err.phase().map(|phase| phase.to_string()),
+                        error: err.to_string(),
+                    });
+                }
+            }
+        }
+
+        report.totals = Totals {
+            tables: report.tables.len(),
+            failed_tables: report
+                .failures
+                .iter()
+                .filter_map(|failure| failure.table.as_ref())
+                .collect::<BTreeSet<_>>()
+                .len(),
+            skipped_tables: report.skipped.len(),
+            rows_read: report.tables.values().map(|table| table.rows_read).sum(),
+            rows_written: report.tables.values().map(|table| table.rows_written).sum(),
+            bytes: report.tables.values().map(|table| table.bytes).sum(),
+        };
+
+        let Some(path) = &state.path else {
+            return;
+        };
+
+        let written = serde_json::to_string_pretty(report)
+            .map_err(|err| err.to_string())
+            .and_then(|json| fs::write(path, json).map_err(|err| err.to_string()));
+        match written {
+            Ok(()) => logger.info(format!("Wrote run report to {}", path).as_str()),
+            Err(err) => {
+                logger.error(format!("Failed to write run report {}: {}", path, err).as_str())
+            }
+        }
+    }
+}
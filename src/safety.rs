@@ -0,0 +1,214 @@
+use std::io::{self, BufRead, IsTerminal, Write};
+
+use regex::Regex;
+
+use crate::config::{Config, DbConfig, Engine};
+use crate::error::{CustomError, CustomResult};
+use crate::logger::Logger;
+
+/// Refuses to run against a target that is denied, not allowed, the same as the
+/// source, or not confirmed by the operator.
+pub fn check(config: &Config) -> CustomResult<()> {
+    let logger = Logger::new();
+    let target = target_name(&config.target);
+
+    if let Some(pattern) = find_match(&config.safety.denied_targets, &config.target)? {
+        return Err(CustomError::SafetyCheck(format!(
+            "target {} matches denied_targets entry `{}`",
+            target, pattern
+        )));
+    }
+
+    if !config.safety.allowed_targets.is_empty()
+        && find_match(&config.safety.allowed_targets, &config.target)?.is_none()
+    {
+        return Err(CustomError::SafetyCheck(format!(
+            "target {} is not listed in allowed_targets",
+            target
+        )));
+    }
+
+    let engine = config.technology.category;
+    if identity(&config.source, engine) == identity(&config.target, engine) {
+        return Err(CustomError::SafetyCheck(format!(
+            "source and target resolve to the same database ({})",
+            target
+        )));
+    }
+
+    if config.run.dry_run {
+        logger.debug("Skipping target confirmation for --dry-run");
+        return Ok(());
+    }
+    if config.run.assume_yes {
+        logger.warn(format!("Target {} confirmed with --yes", target).as_str());
+        return Ok(());
+    }
+
+    confirm(&config.target.database, &target)
+}
+
+fn confirm(database: &str, target: &str) -> CustomResult<()> {
+    if !io::stdin().is_terminal() {
+        return Err(CustomError::SafetyCheck(
+            "no terminal to confirm the target; pass --yes to run unattended".to_string(),
+        ));
+    }
+
+    print!(
+        "This will overwrite {}.\nType the target database name to continue: ",
+        target
+    );
+    io::stdout()
+        .flush()
+        .map_err(|err| CustomError::SafetyCheck(format!("failed to prompt: {}", err)))?;
+
+    let mut answer = String::new();
+    io::stdin()
+        .lock()
+        .read_line(&mut answer)
+        .map_err(|err| CustomError::SafetyCheck(format!("failed to read answer: {}", err)))?;
+
+    if answer.trim() != database {
+        return Err(CustomError::SafetyCheck(
+            "confirmation did not match the target database name".to_string(),
+        ));
+    }
+
+    Ok(())
+}
+
+fn target_name(db_config: &DbConfig) -> String {
+    format!("{}/{}", host(db_config), db_config.database)
+}
+
+fn host(db_config: &DbConfig) -> String {
+    normalize_host(&db_config.host)
+}
+
+// Spellings of the local machine compare equal, in hosts and in safety patterns alike.
+fn normalize_host(host: &str) -> String {
+    match host.to_lowercase().as_str() {
+        "" | "localhost" | "127.0.0.1" | "::1" => "localhost".to_string(),
+        host => host.to_string(),
+    }
+}
+
+// Host, port and database (plus schema on postgres, where only the schema is replaced).
+fn identity(db_config: &DbConfig, engine: Engine) -> (String, String, String, Option<String>) {
+    let port = match (db_config.port.as_str(), engine) {
+        ("", Engine::Postgres) => "5432".to_string(),
+        ("", Engine::Mysql) => "3306".to_string(),
+        (port, _) => port.to_string(),
+    };
+    let schema = match engine {
+        Engine::Postgres => db_config.schema.clone(),
+        Engine::Mysql => None,
+    };
+
+    (host(db_config), port, db_config.database.clone(), schema)
+}
+
+/// Entries are `host` or `host/database`; `*` matches any run of characters.
+fn find_match<'a>(patterns: &'a [String], db_config: &DbConfig) -> CustomResult<Option<&'a str>> {
+    let host = host(db_config);
+    for pattern in patterns {
+        let (host_pattern, database_pattern) = match pattern.split_once('/') {
+            Some((host, database)) => (host, Some(database)),
+            None => (pattern.as_str(), None),
+        };
+
+        let host_matches = glob_matches(&normalize_host(host_pattern), &host)?;
+        let database_matches = match database_pattern {
+            Some(database_pattern) => glob_matches(database_pattern, &db_config.database)?,
+            None => true,
+        };
+
+        if host_matches && database_matches {
+            return Ok(Some(pattern));
+        }
+    }
+
+    Ok(None)
+}
+
+// Case-insensitive, so a deny entry can't be sidestepped by spelling the host differently.
+fn glob_matches(pattern: &str, value: &str) -> CustomResult<bool> {
+    let expression = format!(
+        "^{}$",
+        regex::escape(&pattern.to_lowercase()).replace(r"\*", ".*")
+    );
+    let regex = Regex::new(&expression).map_err(|err| {
+        CustomError::Config(format!("Invalid safety pattern `{}`: {}", pattern, err))
+    })?;
+
+    Ok(regex.is_match(&value.to_lowercase()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{find_match, glob_matches};
+    use crate::config::DbConfig;
+
+    fn target(host: &str, database: &str) -> DbConfig {
+        toml::from_str(&format!("host = {:?}\ndatabase = {:?}", host, database)).unwrap()
+    }
+
+    fn patterns(entries: &[&str]) -> Vec<String> {
+        entries.iter().map(|entry| entry.to_string()).collect()
+    }
+
+    #[test]
+    fn glob_matches_whole_value_case_insensitively() {
+        assert!(glob_matches("prod-db", "PROD-DB").unwrap());
+        assert!(glob_matches("*.prod.internal", "db1.prod.internal").unwrap());
+        assert!(glob_matches("db-*-replica", "db-eu-replica").unwrap());
+        assert!(!glob_matches("prod", "prod-db").unwrap());
+        assert!(!glob_matches("*.prod.internal", "prod.internal").unwrap());
+    }
+
+    #[test]
+    fn glob_matches_takes_regex_characters_literally() {
+        assert!(glob_matches("db.prod", "db.prod").unwrap());
+        assert!(!glob_matches("db.prod", "dbxprod").unwrap());
+        assert!(glob_matches("(db)+", "(db)+").unwrap());
+    }
+
+    #[test]
+    fn find_match_by_host_or_host_and_database() {
+        let entries = patterns(&["staging", "prod.internal/app*"]);
+
+        assert_eq!(
+            find_match(&entries, &target("staging", "anything")).unwrap(),
+            Some("staging")
+        );
+        assert_eq!(
+            find_match(&entries, &target("prod.internal", "app_main")).unwrap(),
+            Some("prod.internal/app*")
+        );
+        assert_eq!(
+            find_match(&entries, &target("prod.internal", "reports")).unwrap(),
+            None
+        );
+    }
+
+    #[test]
+    fn find_match_normalizes_local_hosts_in_patterns() {
+        for host in ["127.0.0.1", "::1", "localhost", ""] {
+            for pattern in ["127.0.0.1", "::1", "localhost", "LOCALHOST/prod"] {
+                let entries = patterns(&[pattern]);
+                assert_eq!(
+                    find_match(&entries, &target(host, "prod")).unwrap(),
+                    Some(pattern),
+                    "{} against {}",
+                    pattern,
+                    host
+                );
+            }
+        }
+        assert_eq!(
+            find_match(&patterns(&["127.0.0.1/prod"]), &target("127.0.0.1", "dev")).unwrap(),
+            None
+        );
+    }
+}
@@ -1,6 +1,21 @@
+use crate::config::Config;
+use crate::diff::SchemaDiff;
 use crate::error::CustomResult;
+use crate::report::SkipReason;
 use regex::Regex;
 
+pub trait MigratorTrait {
+    fn new(config: Config) -> Self;
+
+    async fn migrate(&self) -> CustomResult<()>;
+
+    /// Compares the tables of the source and the target without changing either.
+    async fn verify(&self) -> CustomResult<()>;
+
+    /// Compares the structure of the source and the target without changing either.
+    async fn diff(&self) -> CustomResult<SchemaDiff>;
+}
+
 pub trait StructureMigratorTrait {
     async fn migrate(&self) -> CustomResult<()>;
 
@@ -9,8 +24,17 @@ pub trait StructureMigratorTrait {
     fn skip_table(&self, table_name: &str) -> bool {
         let pattern = Regex::new(r"^\w+_\d+(_\d+)?(_\w+)?$").unwrap();
 
-        let result = pattern.is_match(table_name);
+        pattern.is_match(table_name)
+    }
 
-        result
+    /// Why a table is left out of the structure copy, if it is.
+    fn skip_reason(&self, table_name: &str) -> Option<SkipReason> {
+        if self.is_private_table(table_name) {
+            Some(SkipReason::Private)
+        } else if self.skip_table(table_name) {
+            Some(SkipReason::Pattern)
+        } else {
+            None
+        }
     }
 }
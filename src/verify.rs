@@ -0,0 +1,90 @@
+use std::collections::{BTreeMap, BTreeSet};
+
+/// Hex digits of a key's md5 that pick its bucket (256 buckets) when looking for
+/// the rows that differ.
+pub const BUCKET_DIGITS: usize = 2;
+
+/// Row count and checksum of one side of a table, or of one bucket of it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Summary {
+    pub rows: i64,
+    /// Sum of the first 64 bits of every row's md5, so row order doesn't matter.
+    /// `None` when only counts are compared.
+    pub checksum: Option<String>,
+}
+
+/// Describes how the column names (sorted) of both sides differ, if they do.
+pub fn compare_columns(source: &[String], target: &[String]) -> Option<String> {
+    if source.is_empty() {
+        return Some("table is missing in the source".to_string());
+    }
+    if target.is_empty() {
+        return Some("table is missing in the target".to_string());
+    }
+    if source == target {
+        return None;
+    }
+
+    let source_set: BTreeSet<&String> = source.iter().collect();
+    let target_set: BTreeSet<&String> = target.iter().collect();
+    Some(format!(
+        "columns differ, only in source: {:?}, only in target: {:?}",
+        source_set.difference(&target_set).collect::<Vec<_>>(),
+        target_set.difference(&source_set).collect::<Vec<_>>()
+    ))
+}
+
+/// Describes how the two sides of a table differ, if they do.
+pub fn compare(source: &Summary, target: &Summary) -> Option<String> {
+    if source.rows != target.rows {
+        return Some(format!(
+            "source has {} rows, target has {}",
+            source.rows, target.rows
+        ));
+    }
+    if source.checksum != target.checksum {
+        return Some(format!(
+            "both sides have {} rows but their checksums differ",
+            source.rows
+        ));
+    }
+    None
+}
+
+/// Buckets present on either side whose summaries differ.
+pub fn differing_buckets(
+    source: &BTreeMap<String, Summary>,
+    target: &BTreeMap<String, Summary>,
+) -> Vec<String> {
+    source
+        .keys()
+        .chain(target.keys())
+        .collect::<BTreeSet<_>>()
+        .into_iter()
+        .filter(|bucket| source.get(*bucket) != target.get(*bucket))
+        .cloned()
+        .collect()
+}
+
+/// Up to `limit` keys whose row hash differs between the `key -> hash` maps of both
+/// sides, labelled with what happened to them.
+pub fn differing_keys(
+    source: &BTreeMap<String, String>,
+    target: &BTreeMap<String, String>,
+    limit: usize,
+) -> Vec<String> {
+    let mut keys = Vec::new();
+    for (key, hash) in source {
+        match target.get(key) {
+            None => keys.push(format!("{} (missing)", key)),
+            Some(target_hash) if target_hash != hash => keys.push(format!("{} (changed)", key)),
+            Some(_) => {}
+        }
+    }
+    for key in target.keys().filter(|key| !source.contains_key(*key)) {
+        keys.push(format!("{} (extra)", key));
+    }
+
+    keys.truncate(limit);
+    keys
+}
@@ -0,0 +1,115 @@
+/// Table on the target holding, for every incremental table, the largest watermark
+/// value copied so far.
+pub const STATE_TABLE: &str = "db_copy_watermarks";
+
+/// Rows of an incremental table copied by one run: above the watermark stored by the
+/// last run, up to the largest value the source held when the copy started.
+#[derive(Debug, Clone)]
+pub struct Increment {
+    /// Stored by the last run; `None` copies every row up to `next`.
+    pub last: Option<String>,
+    /// Stored once the rows are copied; `None` when the source has no rows.
+    pub next: Option<String>,
+    /// SQL condition selecting the rows of this run.
+    pub condition: String,
+}
+
+/// Condition on `expression` for the rows above the stored watermark, rendered with
+/// `literal`. The largest watermark of a run is read from these rows only, so a source
+/// whose values went down never moves the stored one back.
+pub fn above(expression: &str, last: Option<&str>, literal: impl Fn(&str) -> String) -> String {
+    match last {
+        Some(last) => format!("{} > {}", expression, literal(last)),
+        None => "TRUE".to_string(),
+    }
+}
+
+impl Increment {
+    /// Builds the condition on `expression`, rendering the bounds with `literal`.
+    /// Nothing is copied, and nothing stored, unless `next` is past `last`.
+    pub fn new(
+        expression: &str,
+        last: Option<String>,
+        next: Option<String>,
+        literal: impl Fn(&str) -> String,
+    ) -> Self {
+        let next = next.filter(|next| last.as_ref() != Some(next));
+        let condition = match (&last, &next) {
+            (Some(last), Some(next)) => format!(
+                "{0} > {1} AND {0} <= {2}",
+                expression,
+                literal(last),
+                literal(next)
+            ),
+            (None, Some(next)) => format!("{} <= {}", expression, literal(next)),
+            (_, None) => "FALSE".to_string(),
+        };
+
+        Self {
+            last,
+            next,
+            condition,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{above, Increment};
+
+    fn literal(value: &str) -> String {
+        format!("'{}'", value)
+    }
+
+    fn increment(last: Option<&str>, next: Option<&str>) -> Increment {
+        Increment::new(
+            "\"id\"",
+            last.map(str::to_string),
+            next.map(str::to_string),
+            literal,
+        )
+    }
+
+    #[test]
+    fn first_run_copies_every_row_up_to_the_source_maximum() {
+        assert_eq!(above("\"id\"", None, literal), "TRUE");
+
+        let increment = increment(None, Some("42"));
+        assert_eq!(increment.condition, "\"id\" <= '42'");
+        assert_eq!(increment.last, None);
+        assert_eq!(increment.next.as_deref(), Some("42"));
+    }
+
+    #[test]
+    fn later_run_copies_rows_between_the_watermarks() {
+        assert_eq!(above("\"id\"", Some("42"), literal), "\"id\" > '42'");
+
+        let increment = increment(Some("42"), Some("50"));
+        assert_eq!(increment.condition, "\"id\" > '42' AND \"id\" <= '50'");
+        assert_eq!(increment.next.as_deref(), Some("50"));
+    }
+
+    #[test]
+    fn equal_watermarks_copy_and_store_nothing() {
+        let increment = increment(Some("42"), Some("42"));
+        assert_eq!(increment.condition, "FALSE");
+        assert_eq!(increment.last.as_deref(), Some("42"));
+        assert_eq!(increment.next, None);
+    }
+
+    #[test]
+    fn source_below_the_stored_watermark_keeps_it() {
+        // Nothing above the stored value: the maximum read with `above` is NULL
+        let increment = increment(Some("42"), None);
+        assert_eq!(increment.condition, "FALSE");
+        assert_eq!(increment.last.as_deref(), Some("42"));
+        assert_eq!(increment.next, None);
+    }
+
+    #[test]
+    fn empty_source_copies_nothing() {
+        let increment = increment(None, None);
+        assert_eq!(increment.condition, "FALSE");
+        assert_eq!(increment.next, None);
+    }
+}